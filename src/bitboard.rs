@@ -0,0 +1,296 @@
+//! Bitboard attack generation.
+//!
+//! A [`Bitboard`] has one bit per square, bit `square_index(position)`, so
+//! bit 0 is a8 and bit 63 is h1. Knight, king and pawn attacks come from
+//! precomputed tables, rook and bishop attacks from magic bitboards.
+
+use std::sync::OnceLock;
+
+use crate::piece::{self, Board, Position, Side};
+
+/// A set of squares, one bit per square
+pub type Bitboard = u64;
+
+/// The bit for a single position
+pub fn bit(position: Position) -> Bitboard {
+    1 << piece::square_index(position)
+}
+
+/// The position of a square index
+pub fn square_position(square: usize) -> Position {
+    (square / 8, square % 8)
+}
+
+/// Iterates over the square indexes set in `bitboard`, lowest first
+pub fn squares(mut bitboard: Bitboard) -> impl Iterator<Item = usize> {
+    std::iter::from_fn(move || {
+        if bitboard == 0 {
+            return None;
+        }
+        let square = bitboard.trailing_zeros() as usize;
+        bitboard &= bitboard - 1;
+        Some(square)
+    })
+}
+
+/// Index of a side in per-side tables
+pub fn side_index(side: &Side) -> usize {
+    match side {
+        Side::White => 0,
+        Side::Black => 1,
+    }
+}
+
+/// One bitboard per side and piece type, kept alongside the Board
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Bitboards {
+    /// Indexed by side_index and then typ_index
+    pub pieces: [[Bitboard; 6]; 2],
+}
+
+impl Bitboards {
+    pub fn from_board(board: &Board) -> Bitboards {
+        let mut bitboards = Bitboards { pieces: [[0; 6]; 2] };
+        for piece in board.iter().flatten() {
+            bitboards.put(&piece.side, piece.typ_index, piece::square_index(piece.position));
+        }
+        bitboards
+    }
+
+    pub fn put(&mut self, side: &Side, typ_index: usize, square: usize) {
+        self.pieces[side_index(side)][typ_index] |= 1 << square;
+    }
+
+    pub fn remove(&mut self, side: &Side, typ_index: usize, square: usize) {
+        self.pieces[side_index(side)][typ_index] &= !(1 << square);
+    }
+
+    /// Every square holding a piece of `side`
+    pub fn side(&self, side: &Side) -> Bitboard {
+        self.pieces[side_index(side)].iter().fold(0, |all, bitboard| all | bitboard)
+    }
+
+    /// Every square holding a piece
+    pub fn occupied(&self) -> Bitboard {
+        self.side(&Side::White) | self.side(&Side::Black)
+    }
+
+    /// Whether any piece of `by` attacks `square`. Looks outwards from the
+    /// square with each piece's attack pattern and checks for a matching piece.
+    pub fn is_attacked(&self, square: usize, by: &Side) -> bool {
+        let theirs = &self.pieces[side_index(by)];
+        let occupied = self.occupied();
+        let rooks = theirs[piece::ROOK_INDEX] | theirs[piece::QUEEN_INDEX];
+        let bishops = theirs[piece::BISHOP_INDEX] | theirs[piece::QUEEN_INDEX];
+        // A pawn of `by` attacks `square` if a pawn of the other side on `square` would attack it
+        pawn_attacks(&by.opponent(), square) & theirs[piece::PAWN_INDEX] != 0
+            || knight_attacks(square) & theirs[piece::KNIGHT_INDEX] != 0
+            || king_attacks(square) & theirs[piece::KING_INDEX] != 0
+            || rook_attacks(square, occupied) & rooks != 0
+            || bishop_attacks(square, occupied) & bishops != 0
+    }
+
+    /// The square of the king of `side`, if it has one
+    pub fn king_square(&self, side: &Side) -> Option<usize> {
+        squares(self.pieces[side_index(side)][piece::KING_INDEX]).next()
+    }
+}
+
+pub fn knight_attacks(square: usize) -> Bitboard {
+    tables().knight[square]
+}
+
+pub fn king_attacks(square: usize) -> Bitboard {
+    tables().king[square]
+}
+
+/// The squares a pawn of `side` on `square` captures on
+pub fn pawn_attacks(side: &Side, square: usize) -> Bitboard {
+    tables().pawn[side_index(side)][square]
+}
+
+pub fn rook_attacks(square: usize, occupied: Bitboard) -> Bitboard {
+    tables().rook[square].attacks(occupied)
+}
+
+pub fn bishop_attacks(square: usize, occupied: Bitboard) -> Bitboard {
+    tables().bishop[square].attacks(occupied)
+}
+
+/// The squares a piece of type `typ_index` attacks from `square`. Pawns are
+/// handled by pawn_attacks since their attacks depend on the side.
+pub fn attacks(typ_index: usize, square: usize, occupied: Bitboard) -> Bitboard {
+    match typ_index {
+        piece::ROOK_INDEX => rook_attacks(square, occupied),
+        piece::KNIGHT_INDEX => knight_attacks(square),
+        piece::BISHOP_INDEX => bishop_attacks(square, occupied),
+        piece::QUEEN_INDEX => rook_attacks(square, occupied) | bishop_attacks(square, occupied),
+        piece::KING_INDEX => king_attacks(square),
+        _ => 0,
+    }
+}
+
+/// Every square a queen on `square` would reach on an empty board
+pub fn queen_rays(square: usize) -> Bitboard {
+    rook_attacks(square, 0) | bishop_attacks(square, 0)
+}
+
+const ROOK_DIRECTIONS: [(isize, isize); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+const BISHOP_DIRECTIONS: [(isize, isize); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+const KNIGHT_JUMPS: [(isize, isize); 8] = [(-2, -1), (-2, 1), (2, -1), (2, 1), (-1, -2), (1, -2), (-1, 2), (1, 2)];
+const KING_STEPS: [(isize, isize); 8] = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
+
+// The magic multiplier for one square. (occupied & mask) * magic >> shift
+// is a perfect hash of the blockers into that square's slice of attacks.
+struct Magic {
+    mask: Bitboard,
+    magic: u64,
+    shift: u32,
+    attacks: Vec<Bitboard>,
+}
+
+impl Magic {
+    fn attacks(&self, occupied: Bitboard) -> Bitboard {
+        self.attacks[((occupied & self.mask).wrapping_mul(self.magic) >> self.shift) as usize]
+    }
+}
+
+struct Tables {
+    knight: [Bitboard; 64],
+    king: [Bitboard; 64],
+    pawn: [[Bitboard; 64]; 2],
+    rook: Vec<Magic>,
+    bishop: Vec<Magic>,
+}
+
+fn tables() -> &'static Tables {
+    static TABLES: OnceLock<Tables> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        let mut tables = Tables {
+            knight: [0; 64],
+            king: [0; 64],
+            pawn: [[0; 64]; 2],
+            rook: vec![],
+            bishop: vec![],
+        };
+        for square in 0..64 {
+            tables.knight[square] = step_attacks(square, &KNIGHT_JUMPS);
+            tables.king[square] = step_attacks(square, &KING_STEPS);
+            tables.pawn[0][square] = step_attacks(square, &[(-1, -1), (-1, 1)]);
+            tables.pawn[1][square] = step_attacks(square, &[(1, -1), (1, 1)]);
+            tables.rook.push(build_magic(square, &ROOK_DIRECTIONS, ROOK_MAGICS[square]));
+            tables.bishop.push(build_magic(square, &BISHOP_DIRECTIONS, BISHOP_MAGICS[square]));
+        }
+        tables
+    })
+}
+
+fn offset(square: usize, (row_step, column_step): (isize, isize)) -> Option<usize> {
+    let (row, column) = square_position(square);
+    let row = row.checked_add_signed(row_step).filter(|row| *row < 8)?;
+    let column = column.checked_add_signed(column_step).filter(|column| *column < 8)?;
+    Some(piece::square_index((row, column)))
+}
+
+fn step_attacks(square: usize, steps: &[(isize, isize)]) -> Bitboard {
+    steps.iter().filter_map(|step| offset(square, *step)).fold(0, |all, target| all | 1 << target)
+}
+
+// Slides from `square` in each direction until the edge or the first blocker,
+// which is included. Slow, only used to fill the magic tables.
+fn slide_attacks(square: usize, directions: &[(isize, isize)], occupied: Bitboard) -> Bitboard {
+    let mut attacks = 0;
+    for direction in directions {
+        let mut current = square;
+        while let Some(next) = offset(current, *direction) {
+            attacks |= 1 << next;
+            if occupied & (1 << next) != 0 {
+                break;
+            }
+            current = next;
+        }
+    }
+    attacks
+}
+
+// The squares whose occupancy matters for a slider on `square`. The last
+// square of each ray never blocks anything beyond it, so it's left out.
+fn relevant_mask(square: usize, directions: &[(isize, isize)]) -> Bitboard {
+    let mut mask = 0;
+    for direction in directions {
+        let mut current = square;
+        while let Some(next) = offset(current, *direction) {
+            if offset(next, *direction).is_none() {
+                break;
+            }
+            mask |= 1 << next;
+            current = next;
+        }
+    }
+    mask
+}
+
+// Fills the attack table for a slider on `square` using a known magic
+fn build_magic(square: usize, directions: &[(isize, isize)], magic: u64) -> Magic {
+    let mask = relevant_mask(square, directions);
+    let bits = mask.count_ones();
+    let shift = 64 - bits;
+    let mut attacks = vec![0; 1 << bits];
+    let mut filled = vec![false; 1 << bits];
+
+    // Every subset of the mask (carry-rippler)
+    let mut subset: Bitboard = 0;
+    loop {
+        let attack = slide_attacks(square, directions, subset);
+        let index = (subset.wrapping_mul(magic) >> shift) as usize;
+        assert!(!filled[index] || attacks[index] == attack, "magic for square {} has a collision", square);
+        filled[index] = true;
+        attacks[index] = attack;
+        subset = subset.wrapping_sub(mask) & mask;
+        if subset == 0 {
+            break;
+        }
+    }
+    Magic { mask, magic, shift, attacks }
+}
+
+// Found by trying random sparse numbers (three random u64s and-ed together)
+// until one mapped every blocker subset of the square without a harmful
+// collision. build_magic checks them again when the tables are filled.
+const ROOK_MAGICS: [u64; 64] = [
+    0x2080002080400010, 0x00C0002001401000, 0x2100110008402002, 0x0880080081041000,
+    0x0200020020041008, 0x2300040008010012, 0x0C00283004008201, 0x0180010000407A80,
+    0x0168800080400020, 0x0010400040201000, 0x1001002001001048, 0x1001002408100100,
+    0x0801000408010012, 0x4001000209000400, 0x08A20004C8020001, 0x2002801145002280,
+    0x0080860021004200, 0x001000C009402002, 0x00B0002004002800, 0x100A808010020800,
+    0x9400808004000800, 0x0090808004000200, 0x0000040010810208, 0x2000020000448534,
+    0x4104400480008033, 0x0000810100204000, 0x0440430900200010, 0x4600240900100100,
+    0x0804080100110004, 0x0001000300080400, 0x0004084400011002, 0x0023040200008041,
+    0x0580050043002080, 0x0400804002802008, 0x0001002001004010, 0x0080200A02001040,
+    0x600D480280802400, 0x400B800201800C00, 0x2408211004004208, 0x0200211082000844,
+    0x0020804010208000, 0x5030004020104000, 0xA042084080220010, 0x4088080010008080,
+    0x5002080100110004, 0x2012002010040400, 0x0040318210440008, 0x0120941040820001,
+    0x1000800100402100, 0x0040002010004840, 0x8108450020001900, 0x0200204008120200,
+    0x0080800C00180180, 0x0885000400420900, 0x230802011008C400, 0x3801740891432200,
+    0x0A00250212024082, 0x0000882040001105, 0x0042102082000A42, 0xC401210810000501,
+    0x0241001002480005, 0x0081000400880241, 0x0000009008024124, 0x0048122980410402,
+];
+
+const BISHOP_MAGICS: [u64; 64] = [
+    0x1862221006220044, 0x2104A14202020060, 0x2804081220444001, 0x2102408900010001,
+    0x0002021000040002, 0x08C3100805004300, 0x1084040124920050, 0x8900440043382010,
+    0x2401410802140040, 0x0901200454208020, 0x0000090216020541, 0x1283844040800804,
+    0x0521840420000803, 0x0800010402400C40, 0x0000408E10100404, 0x0009810048420800,
+    0x2004211004286808, 0x13080A1001380080, 0x0008801004220020, 0x0024000802480800,
+    0x1461001190400401, 0x0020400200500440, 0x0003000409019000, 0x000C20820D011802,
+    0x000804002164100C, 0x00048400A0011404, 0x5018110308044100, 0x0048A00804010020,
+    0x0007840000802000, 0x8808A20075004220, 0x8014040000822100, 0x110C03000E251101,
+    0x0081094820202010, 0x0008041000044100, 0x00C1202808940800, 0x8108100821040400,
+    0x1240010010010041, 0x0810004080011000, 0x00A20C0401804A00, 0x40014C0020050500,
+    0x5805082012042480, 0x2004022144031000, 0x2082002024204808, 0x0800004200800800,
+    0x0410020204100A02, 0x80C1204080804101, 0x0010104E01800042, 0x000800810C400208,
+    0x100080B008201210, 0x8000440605112101, 0x000202008C440040, 0x9004002210442200,
+    0x2032014088222045, 0x0C00202222C20000, 0x0140040820A50100, 0x0222104C29024018,
+    0x0200110121202004, 0x0800104200B00802, 0x0000401424020801, 0x4000000004208840,
+    0x0802E00040104100, 0x03000020A0424080, 0x0011C00408188121, 0x0848020822040013,
+];
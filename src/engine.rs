@@ -0,0 +1,330 @@
+//! Picking a move: a negamax alpha-beta search of the move tree, deepened
+//! one ply at a time until it runs out of depth or time, or is told to stop.
+//! Captures are played out at the leaves by a quiescence search, and moves
+//! are tried best-looking first: the previous best line, then the move the
+//! transposition table remembers, then captures by most valuable victim and
+//! least valuable attacker, then killer moves.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::eval;
+use crate::piece::{Game, Move, PAWN_INDEX};
+use crate::transposition::{Bound, Entry, TranspositionTable};
+
+/// Score of being checkmated right now. Mates further away score closer to
+/// zero, so the search goes for the quickest mate.
+pub const MATE: i32 = 100_000;
+
+// Anything this close to MATE is a forced mate
+const MATE_BOUND: i32 = MATE - 1000;
+
+// Beyond any real score
+const INFINITY: i32 = MATE + 1;
+
+// How much a piece type is worth for ordering captures, indexed by typ_index.
+// Unlike PIECE_VALUES the king counts, as the most valuable attacker.
+const MVV_LVA_RANKS: [i32; 6] = [1, 4, 2, 3, 5, 6];
+
+// Move ordering bonuses, best first
+const PV_BONUS: i32 = 100_000;
+const HASH_MOVE_BONUS: i32 = 50_000;
+const CAPTURE_BONUS: i32 = 10_000;
+const PROMOTION_BONUS: i32 = 9_000;
+const KILLER_BONUS: i32 = 8_000;
+
+/// When a search has to stop. With neither a depth nor a time it goes on
+/// until the stop flag is set.
+#[derive(Debug, Default, Clone)]
+pub struct SearchLimits {
+    /// Deepest iteration to search, in plies
+    pub depth: Option<usize>,
+    /// How long to search for
+    pub time: Option<Duration>,
+}
+
+/// What the search found after finishing one depth
+#[derive(Debug, Clone)]
+pub struct SearchInfo {
+    pub depth: usize,
+    /// Centipawns from the point of view of the side to move, or MATE minus
+    /// the number of plies to a mate
+    pub score: i32,
+    pub nodes: u64,
+    pub time: Duration,
+    /// The best line found, starting with the move to play
+    pub pv: Vec<Move>,
+}
+
+impl SearchInfo {
+    /// Moves to mate if the score is a forced mate, negative when the side
+    /// to move is the one getting mated
+    pub fn mate_in(&self) -> Option<i32> {
+        if self.score >= MATE_BOUND {
+            Some((MATE - self.score + 1) / 2)
+        } else if self.score <= -MATE_BOUND {
+            Some(-(MATE + self.score) / 2)
+        } else {
+            None
+        }
+    }
+}
+
+/// Searches `game` until `limits` or `stop` say to stop, calling `report`
+/// after each depth is finished. Returns the best move found, None if the
+/// side to move has no legal moves. The first depth always gets finished, so
+/// there is a move even if `stop` is set straight away. Results are kept in
+/// `table`, which can be reused for the next search of the same game.
+pub fn search(game: &Game, limits: &SearchLimits, table: &TranspositionTable, stop: &AtomicBool, mut report: impl FnMut(&SearchInfo)) -> Option<Move> {
+    table.new_search();
+    let mut search = Search {
+        game: game.clone(),
+        table,
+        nodes: 0,
+        start: Instant::now(),
+        deadline: limits.time.map(|time| Instant::now() + time),
+        stop,
+        abortable: false,
+        aborted: false,
+        previous_pv: vec![],
+        killers: vec![],
+    };
+    let mut best = None;
+    let mut depth = 1;
+    loop {
+        search.abortable = depth > 1;
+        let mut pv = vec![];
+        let score = search.negamax(depth, 0, -INFINITY, INFINITY, &mut pv);
+        // A search cut short only looked at some of the moves, so its result can't be trusted
+        if search.aborted {
+            break;
+        }
+        best = pv.first().copied();
+        report(&SearchInfo { depth, score, nodes: search.nodes, time: search.start.elapsed(), pv: pv.clone() });
+        if best.is_none() || limits.depth.is_some_and(|limit| depth >= limit) {
+            break;
+        }
+        // A forced mate won't get any shorter by searching deeper
+        if score.abs() >= MATE_BOUND && (MATE - score.abs()) as usize <= depth {
+            break;
+        }
+        search.previous_pv = pv;
+        depth += 1;
+    }
+    best
+}
+
+// State shared by the whole search
+struct Search<'a> {
+    game: Game,
+    table: &'a TranspositionTable,
+    nodes: u64,
+    start: Instant,
+    deadline: Option<Instant>,
+    stop: &'a AtomicBool,
+    // Whether running out of time or being stopped counts yet, not until there's a move
+    abortable: bool,
+    // Set once time ran out or we were told to stop, everything after is thrown away
+    aborted: bool,
+    // The best line of the last finished depth, tried first this time round
+    previous_pv: Vec<Move>,
+    // Per ply, the last two quiet moves that caused a beta cutoff. A move that
+    // refuted one position often refutes its siblings too.
+    killers: Vec<[Option<Move>; 2]>,
+}
+
+impl Search<'_> {
+    // Counts a node, and every so often checks whether we have to stop
+    fn visit(&mut self) {
+        self.nodes += 1;
+        // Checking the clock is slow compared to a node, so only do it now and then
+        if self.abortable && self.nodes.is_multiple_of(1024) && (self.stop.load(Ordering::Relaxed) || self.deadline.is_some_and(|deadline| Instant::now() >= deadline)) {
+            self.aborted = true;
+        }
+    }
+
+    // The score of the position for the side to move, looking `depth` plies
+    // ahead, as long as it's between `alpha` and `beta`. Otherwise it's only
+    // a bound: at most alpha, or at least beta. `ply` is how far we are from
+    // the root. The best line is put in `pv`.
+    fn negamax(&mut self, depth: usize, ply: usize, mut alpha: i32, beta: i32, pv: &mut Vec<Move>) -> i32 {
+        // Draws by the fifty-move rule or repetition, the first repetition is enough
+        if ply > 0 && (self.game.halfmove_clock >= 100 || self.game.repetitions() >= 2) {
+            return 0;
+        }
+        if depth == 0 {
+            return self.quiescence(ply, alpha, beta);
+        }
+        self.visit();
+
+        // A deep enough result from earlier may already settle it. Not at the
+        // root though, where we need the whole line.
+        let entry = self.table.probe(self.game.hash);
+        if let Some(entry) = entry.filter(|entry| ply > 0 && entry.depth >= depth) {
+            let score = score_from_table(entry.score, ply);
+            let settled = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => score >= beta,
+                Bound::Upper => score <= alpha,
+            };
+            if settled {
+                pv.clear();
+                pv.extend(entry.best_move);
+                return score;
+            }
+        }
+
+        let mut moves = self.game.legal_moves();
+        if moves.is_empty() {
+            return if self.game.is_in_check(&self.game.side) { -MATE + ply as i32 } else { 0 };
+        }
+        if self.aborted && ply > 0 {
+            return 0;
+        }
+
+        let hash_move = entry.and_then(|entry| entry.best_move);
+        self.order(&mut moves, ply, hash_move);
+        let original_alpha = alpha;
+        let mut best = -INFINITY;
+        let mut best_move = None;
+        for movement in moves {
+            let undo = self.game.make_move(movement);
+            let mut line = vec![];
+            let score = -self.negamax(depth - 1, ply + 1, -beta, -alpha, &mut line);
+            self.game.unmake_move(undo);
+            if self.aborted {
+                break;
+            }
+            if score > best {
+                best = score;
+                best_move = Some(movement);
+            }
+            if score > alpha {
+                alpha = score;
+                pv.clear();
+                pv.push(movement);
+                pv.extend(line);
+            }
+            if alpha >= beta {
+                if !movement.flags.capture {
+                    self.add_killer(movement, ply);
+                }
+                break;
+            }
+        }
+        // Half searched results aren't worth keeping
+        if !self.aborted {
+            let bound = if best >= beta {
+                Bound::Lower
+            } else if best > original_alpha {
+                Bound::Exact
+            } else {
+                Bound::Upper
+            };
+            self.table.store(self.game.hash, Entry { depth, score: score_to_table(best, ply), bound, best_move });
+        }
+        best
+    }
+
+    // Plays out captures (and promotions) until the position is quiet, so
+    // the evaluation isn't taken in the middle of an exchange. The side to
+    // move can "stand pat" and not capture anything, unless it's in check:
+    // then every way out of check is tried, and having none is mate.
+    fn quiescence(&mut self, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        self.visit();
+        let mut moves = if self.game.is_in_check(&self.game.side) {
+            let evasions = self.game.legal_moves();
+            if evasions.is_empty() {
+                return -MATE + ply as i32;
+            }
+            if self.aborted {
+                return alpha;
+            }
+            evasions
+        } else {
+            // Stalemate isn't looked for here, it's rare enough that evaluating
+            // the position instead costs less than generating every move
+            let stand_pat = eval::evaluate(&self.game);
+            if stand_pat >= beta || self.aborted {
+                return stand_pat;
+            }
+            alpha = alpha.max(stand_pat);
+            self.game.legal_captures()
+        };
+        self.order(&mut moves, ply, None);
+        for movement in moves {
+            let undo = self.game.make_move(movement);
+            let score = -self.quiescence(ply + 1, -beta, -alpha);
+            self.game.unmake_move(undo);
+            if score >= beta {
+                return score;
+            }
+            alpha = alpha.max(score);
+        }
+        alpha
+    }
+
+    // Sorts `moves` so the ones most likely to be best come first
+    fn order(&self, moves: &mut [Move], ply: usize, hash_move: Option<Move>) {
+        let pv_move = self.previous_pv.get(ply);
+        let killers = self.killers.get(ply).copied().unwrap_or_default();
+        moves.sort_by_cached_key(|movement| {
+            let mut bonus = 0;
+            if pv_move == Some(movement) {
+                bonus += PV_BONUS;
+            }
+            if hash_move == Some(*movement) {
+                bonus += HASH_MOVE_BONUS;
+            }
+            if movement.flags.capture {
+                // Most valuable victim first, and of those the least valuable attacker
+                let victim = match self.game.piece_at(movement.to) {
+                    Some(victim) => victim.typ_index,
+                    None => PAWN_INDEX, // en passant
+                };
+                let attacker = self.game.piece_at(movement.from).map_or(PAWN_INDEX, |attacker| attacker.typ_index);
+                bonus += CAPTURE_BONUS + MVV_LVA_RANKS[victim] * 10 - MVV_LVA_RANKS[attacker];
+            } else if killers.contains(&Some(*movement)) {
+                bonus += KILLER_BONUS;
+            }
+            if let Some(promotion) = movement.promotion {
+                bonus += PROMOTION_BONUS + MVV_LVA_RANKS[promotion];
+            }
+            std::cmp::Reverse(bonus)
+        });
+    }
+
+    fn add_killer(&mut self, movement: Move, ply: usize) {
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None, None]);
+        }
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(movement) {
+            killers[1] = killers[0];
+            killers[0] = Some(movement);
+        }
+    }
+}
+
+// Mate scores count plies from the root, but the same position can be reached
+// at any ply, so the table counts them from the position itself instead
+fn score_to_table(score: i32, ply: usize) -> i32 {
+    if score >= MATE_BOUND {
+        score + ply as i32
+    } else if score <= -MATE_BOUND {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+// score_to_table in reverse
+fn score_from_table(score: i32, ply: usize) -> i32 {
+    if score >= MATE_BOUND {
+        score - ply as i32
+    } else if score <= -MATE_BOUND {
+        score + ply as i32
+    } else {
+        score
+    }
+}
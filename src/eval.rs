@@ -0,0 +1,351 @@
+//! Static evaluation: how good a position looks without searching any
+//! further, in centipawns. Every term has a middlegame and an endgame value,
+//! blended by how much material is left (a "tapered" evaluation).
+
+use std::ops::{AddAssign, Sub};
+
+use crate::bitboard::{self, Bitboard, Bitboards};
+use crate::piece::{Game, Side, BISHOP_INDEX, KNIGHT_INDEX, PAWN_INDEX, QUEEN_INDEX, ROOK_INDEX};
+
+// Middlegame and endgame value of each piece type, indexed by typ_index
+const MATERIAL: [Tapered; 6] = [
+    Tapered(82, 94),
+    Tapered(477, 512),
+    Tapered(337, 281),
+    Tapered(365, 297),
+    Tapered(1025, 936),
+    Tapered(0, 0),
+];
+
+// How much each piece type counts towards the game phase, indexed by
+// typ_index. The starting position adds up to PHASE_TOTAL.
+const PHASE_WEIGHTS: [i32; 6] = [0, 2, 1, 1, 4, 0];
+
+/// The game phase with all the pieces on the board
+pub const PHASE_TOTAL: i32 = 24;
+
+// Piece-square tables, from White's side of the board and laid out the way
+// it looks: a8 first, h1 last. Black uses them upside down.
+const PAWN_MIDDLEGAME: [i32; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+    50,  50,  50,  50,  50,  50,  50,  50,
+    10,  10,  20,  30,  30,  20,  10,  10,
+     5,   5,  10,  25,  25,  10,   5,   5,
+     0,   0,   0,  20,  20,   0,   0,   0,
+     5,  -5, -10,   0,   0, -10,  -5,   5,
+     5,  10,  10, -20, -20,  10,  10,   5,
+     0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+// In the endgame all that matters for a pawn is how close it is to queening
+const PAWN_ENDGAME: [i32; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+    80,  80,  80,  80,  80,  80,  80,  80,
+    50,  50,  50,  50,  50,  50,  50,  50,
+    30,  30,  30,  30,  30,  30,  30,  30,
+    15,  15,  15,  15,  15,  15,  15,  15,
+     5,   5,   5,   5,   5,   5,   5,   5,
+     0,   0,   0,   0,   0,   0,   0,   0,
+     0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+const KNIGHT_TABLE: [i32; 64] = [
+   -50, -40, -30, -30, -30, -30, -40, -50,
+   -40, -20,   0,   0,   0,   0, -20, -40,
+   -30,   0,  10,  15,  15,  10,   0, -30,
+   -30,   5,  15,  20,  20,  15,   5, -30,
+   -30,   0,  15,  20,  20,  15,   0, -30,
+   -30,   5,  10,  15,  15,  10,   5, -30,
+   -40, -20,   0,   5,   5,   0, -20, -40,
+   -50, -40, -30, -30, -30, -30, -40, -50,
+];
+
+const BISHOP_TABLE: [i32; 64] = [
+   -20, -10, -10, -10, -10, -10, -10, -20,
+   -10,   0,   0,   0,   0,   0,   0, -10,
+   -10,   0,   5,  10,  10,   5,   0, -10,
+   -10,   5,   5,  10,  10,   5,   5, -10,
+   -10,   0,  10,  10,  10,  10,   0, -10,
+   -10,  10,  10,  10,  10,  10,  10, -10,
+   -10,   5,   0,   0,   0,   0,   5, -10,
+   -20, -10, -10, -10, -10, -10, -10, -20,
+];
+
+const ROOK_TABLE: [i32; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+     5,  10,  10,  10,  10,  10,  10,   5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+     0,   0,   0,   5,   5,   0,   0,   0,
+];
+
+const QUEEN_TABLE: [i32; 64] = [
+   -20, -10, -10,  -5,  -5, -10, -10, -20,
+   -10,   0,   0,   0,   0,   0,   0, -10,
+   -10,   0,   5,   5,   5,   5,   0, -10,
+    -5,   0,   5,   5,   5,   5,   0,  -5,
+     0,   0,   5,   5,   5,   5,   0,  -5,
+   -10,   5,   5,   5,   5,   5,   0, -10,
+   -10,   0,   5,   0,   0,   0,   0, -10,
+   -20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+
+// Tucked away behind its pawns while there are still pieces around...
+const KING_MIDDLEGAME: [i32; 64] = [
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -20, -30, -30, -40, -40, -30, -30, -20,
+   -10, -20, -20, -20, -20, -20, -20, -10,
+    20,  20,   0,   0,   0,   0,  20,  20,
+    20,  30,  10,   0,   0,  10,  30,  20,
+];
+
+// ...and in the middle of the board once they're gone
+const KING_ENDGAME: [i32; 64] = [
+   -50, -40, -30, -20, -20, -30, -40, -50,
+   -30, -20, -10,   0,   0, -10, -20, -30,
+   -30, -10,  20,  30,  30,  20, -10, -30,
+   -30, -10,  30,  40,  40,  30, -10, -30,
+   -30, -10,  30,  40,  40,  30, -10, -30,
+   -30, -10,  20,  30,  30,  20, -10, -30,
+   -30, -30,   0,   0,   0,   0, -30, -30,
+   -50, -30, -30, -30, -30, -30, -30, -50,
+];
+
+// The tables for each typ_index
+const MIDDLEGAME_TABLES: [&[i32; 64]; 6] = [&PAWN_MIDDLEGAME, &ROOK_TABLE, &KNIGHT_TABLE, &BISHOP_TABLE, &QUEEN_TABLE, &KING_MIDDLEGAME];
+const ENDGAME_TABLES: [&[i32; 64]; 6] = [&PAWN_ENDGAME, &ROOK_TABLE, &KNIGHT_TABLE, &BISHOP_TABLE, &QUEEN_TABLE, &KING_ENDGAME];
+
+// Pawn structure
+const DOUBLED_PAWN: Tapered = Tapered(-10, -20);
+const ISOLATED_PAWN: Tapered = Tapered(-10, -15);
+// For a passed pawn, by how many ranks it has advanced from its starting rank
+const PASSED_PAWN: [Tapered; 6] = [
+    Tapered(0, 0),
+    Tapered(5, 10),
+    Tapered(10, 20),
+    Tapered(20, 40),
+    Tapered(35, 65),
+    Tapered(60, 100),
+];
+
+// Per square a piece can move to, indexed by typ_index
+const MOBILITY: [Tapered; 6] = [
+    Tapered(0, 0),
+    Tapered(2, 4),
+    Tapered(4, 4),
+    Tapered(5, 5),
+    Tapered(1, 2),
+    Tapered(0, 0),
+];
+
+// King safety only counts in the middlegame. A bonus for each pawn right in
+// front of the king, and a penalty for each attack on the squares around it
+// by each kind of piece, indexed by typ_index.
+const PAWN_SHIELD: Tapered = Tapered(10, 0);
+const KING_ZONE_ATTACK: [Tapered; 6] = [
+    Tapered(0, 0),
+    Tapered(-6, 0),
+    Tapered(-4, 0),
+    Tapered(-4, 0),
+    Tapered(-10, 0),
+    Tapered(0, 0),
+];
+
+// Every square on the a-file, shift it for the others
+const FILE_A: Bitboard = 0x0101_0101_0101_0101;
+
+/// Each term of the evaluation, from White's point of view and already
+/// tapered by the game phase
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Evaluation {
+    pub material: i32,
+    pub piece_squares: i32,
+    pub pawn_structure: i32,
+    pub mobility: i32,
+    pub king_safety: i32,
+    /// PHASE_TOTAL with all pieces on the board, down to 0 with only kings
+    /// and pawns left
+    pub phase: i32,
+}
+
+impl Evaluation {
+    /// All the terms added up
+    pub fn total(&self) -> i32 {
+        self.material + self.piece_squares + self.pawn_structure + self.mobility + self.king_safety
+    }
+}
+
+/// How good the position is for the side to move, in centipawns
+pub fn evaluate(game: &Game) -> i32 {
+    let total = evaluation(game).total();
+    if game.side == Side::White { total } else { -total }
+}
+
+/// evaluate split up into its terms, from White's point of view
+pub fn evaluation(game: &Game) -> Evaluation {
+    let bitboards = &game.bitboards;
+    let phase = game.pieces().map(|piece| PHASE_WEIGHTS[piece.typ_index]).sum::<i32>().min(PHASE_TOTAL);
+    let term = |score: fn(&Bitboards, &Side) -> Tapered| (score(bitboards, &Side::White) - score(bitboards, &Side::Black)).taper(phase);
+    Evaluation {
+        material: term(material),
+        piece_squares: term(piece_squares),
+        pawn_structure: term(pawn_structure),
+        mobility: term(mobility),
+        king_safety: term(king_safety),
+        phase,
+    }
+}
+
+// A middlegame and an endgame score
+#[derive(Debug, Default, Clone, Copy)]
+struct Tapered(i32, i32);
+
+impl Tapered {
+    // Blends the two scores, all middlegame at PHASE_TOTAL and all endgame at 0
+    fn taper(self, phase: i32) -> i32 {
+        (self.0 * phase + self.1 * (PHASE_TOTAL - phase)) / PHASE_TOTAL
+    }
+
+    fn times(self, count: u32) -> Tapered {
+        Tapered(self.0 * count as i32, self.1 * count as i32)
+    }
+}
+
+impl AddAssign for Tapered {
+    fn add_assign(&mut self, other: Tapered) {
+        self.0 += other.0;
+        self.1 += other.1;
+    }
+}
+
+impl Sub for Tapered {
+    type Output = Tapered;
+
+    fn sub(self, other: Tapered) -> Tapered {
+        Tapered(self.0 - other.0, self.1 - other.1)
+    }
+}
+
+fn pieces(bitboards: &Bitboards, side: &Side, typ_index: usize) -> Bitboard {
+    bitboards.pieces[bitboard::side_index(side)][typ_index]
+}
+
+// The square in the piece-square tables, which are drawn for White
+fn table_square(square: usize, side: &Side) -> usize {
+    if *side == Side::White { square } else { square ^ 56 }
+}
+
+fn material(bitboards: &Bitboards, side: &Side) -> Tapered {
+    let mut score = Tapered::default();
+    for (typ_index, value) in MATERIAL.iter().enumerate() {
+        score += value.times(pieces(bitboards, side, typ_index).count_ones());
+    }
+    score
+}
+
+fn piece_squares(bitboards: &Bitboards, side: &Side) -> Tapered {
+    let mut score = Tapered::default();
+    for (typ_index, (middlegame, endgame)) in MIDDLEGAME_TABLES.iter().zip(ENDGAME_TABLES).enumerate() {
+        for square in bitboard::squares(pieces(bitboards, side, typ_index)) {
+            let square = table_square(square, side);
+            score += Tapered(middlegame[square], endgame[square]);
+        }
+    }
+    score
+}
+
+fn pawn_structure(bitboards: &Bitboards, side: &Side) -> Tapered {
+    let mut score = Tapered::default();
+    let own = pieces(bitboards, side, PAWN_INDEX);
+    let enemy = pieces(bitboards, &side.opponent(), PAWN_INDEX);
+    for column in 0..8 {
+        let on_file = (own & FILE_A << column).count_ones();
+        if on_file > 1 {
+            score += DOUBLED_PAWN.times(on_file - 1);
+        }
+        if on_file > 0 && own & adjacent_files(column) == 0 {
+            score += ISOLATED_PAWN.times(on_file);
+        }
+    }
+    for square in bitboard::squares(own) {
+        let (row, column) = bitboard::square_position(square);
+        // Nothing can stop it but pieces: no enemy pawns in front of it on its own or the next files
+        let files = FILE_A << column | adjacent_files(column);
+        if enemy & files & ahead(row, side) == 0 {
+            let advanced = if *side == Side::White { 6usize.saturating_sub(row) } else { row.saturating_sub(1) };
+            score += PASSED_PAWN[advanced.min(5)];
+        }
+    }
+    score
+}
+
+fn mobility(bitboards: &Bitboards, side: &Side) -> Tapered {
+    let mut score = Tapered::default();
+    let own = bitboards.side(side);
+    let occupied = bitboards.occupied();
+    for typ_index in [ROOK_INDEX, KNIGHT_INDEX, BISHOP_INDEX, QUEEN_INDEX] {
+        for square in bitboard::squares(pieces(bitboards, side, typ_index)) {
+            let moves = bitboard::attacks(typ_index, square, occupied) & !own;
+            score += MOBILITY[typ_index].times(moves.count_ones());
+        }
+    }
+    score
+}
+
+fn king_safety(bitboards: &Bitboards, side: &Side) -> Tapered {
+    let mut score = Tapered::default();
+    let king = match bitboards.king_square(side) {
+        Some(king) => king,
+        None => return score,
+    };
+    let (row, column) = bitboard::square_position(king);
+    // The pawns on the king's file and the ones next to it, one or two ranks up
+    let files = FILE_A << column | adjacent_files(column);
+    let shield_rows = ahead(row, side) & !ahead_by_more_than_two(row, side);
+    score += PAWN_SHIELD.times((pieces(bitboards, side, PAWN_INDEX) & files & shield_rows).count_ones());
+
+    let zone = bitboard::king_attacks(king) | 1 << king;
+    let occupied = bitboards.occupied();
+    for typ_index in [ROOK_INDEX, KNIGHT_INDEX, BISHOP_INDEX, QUEEN_INDEX] {
+        for square in bitboard::squares(pieces(bitboards, &side.opponent(), typ_index)) {
+            let attacked = bitboard::attacks(typ_index, square, occupied) & zone;
+            score += KING_ZONE_ATTACK[typ_index].times(attacked.count_ones());
+        }
+    }
+    score
+}
+
+// The files either side of `column`
+fn adjacent_files(column: usize) -> Bitboard {
+    let mut files = 0;
+    if column > 0 {
+        files |= FILE_A << (column - 1);
+    }
+    if column < 7 {
+        files |= FILE_A << (column + 1);
+    }
+    files
+}
+
+// Every rank in front of `row`, from `side`'s point of view. White moves
+// towards row 0.
+fn ahead(row: usize, side: &Side) -> Bitboard {
+    match side {
+        Side::White => (1u64 << (row * 8)) - 1,
+        Side::Black => if row == 7 { 0 } else { !0 << ((row + 1) * 8) },
+    }
+}
+
+// The ranks more than two in front of `row`
+fn ahead_by_more_than_two(row: usize, side: &Side) -> Bitboard {
+    match side {
+        Side::White => if row < 2 { 0 } else { ahead(row - 2, side) },
+        Side::Black => if row > 5 { 0 } else { ahead(row + 2, side) },
+    }
+}
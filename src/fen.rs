@@ -1,12 +1,63 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt};
 
+use crate::bitboard::Bitboards;
 use crate::piece::{self, Piece};
+use crate::zobrist;
 
 const FEN_SPACE: [char; 8] = ['1', '2', '3', '4', '5', '6', '7', '8'];
 
-pub fn fen_to_board(fen: &String) -> piece::Game {
-    let mut board: Vec<Piece> = vec![];
-    let mut side = piece::Side::White;
+/// Reasons a FEN string can be rejected. Every variant carries the column
+/// (0-based character offset into the FEN string) where the problem was found.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum FenError {
+    /// A character in the placement field that is neither a piece nor a digit
+    InvalidPiece { column: usize, found: char },
+    /// A rank describes more than 8 squares
+    RankOverflow { column: usize },
+    /// A rank describes fewer than 8 squares
+    RankUnderflow { column: usize },
+    /// The placement field doesn't have exactly 8 ranks
+    WrongNumberOfRanks { column: usize, found: usize },
+    /// The side to move is not "w" or "b"
+    InvalidSide { column: usize },
+    /// The castling field is not "-" or a combination of "KQkq"
+    InvalidCastling { column: usize },
+    /// The en passant field is not "-" or the square a pawn just skipped over
+    InvalidEnPassant { column: usize },
+    /// The halfmove clock is not a non-negative number
+    InvalidHalfmoveClock { column: usize },
+    /// The fullmove number is not a positive number
+    InvalidFullmoveNumber { column: usize },
+    /// There is something after the sixth field
+    TooManyFields { column: usize },
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FenError::InvalidPiece { column, found } => write!(f, "invalid piece '{}' at column {}", found, column),
+            FenError::RankOverflow { column } => write!(f, "rank has more than 8 squares at column {}", column),
+            FenError::RankUnderflow { column } => write!(f, "rank has fewer than 8 squares at column {}", column),
+            FenError::WrongNumberOfRanks { column, found } => write!(f, "expected 8 ranks but found {} at column {}", found, column),
+            FenError::InvalidSide { column } => write!(f, "side to move must be 'w' or 'b' at column {}", column),
+            FenError::InvalidCastling { column } => write!(f, "invalid castling availability at column {}", column),
+            FenError::InvalidEnPassant { column } => write!(f, "invalid en passant square at column {}", column),
+            FenError::InvalidHalfmoveClock { column } => write!(f, "invalid halfmove clock at column {}", column),
+            FenError::InvalidFullmoveNumber { column } => write!(f, "invalid fullmove number at column {}", column),
+            FenError::TooManyFields { column } => write!(f, "unexpected field at column {}", column),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+/// The standard starting position
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// Builds a Game from a FEN string. The last four fields may be left out,
+/// in which case there is no castling or en passant and the clocks start fresh.
+pub fn fen_to_board(fen: &str) -> Result<piece::Game, FenError> {
+    let mut board: piece::Board = [None; 64];
 
     let mut fen_black_to_index: HashMap<char, usize> = HashMap::new();
     let mut fen_white_to_index: HashMap<char, usize> = HashMap::new();
@@ -24,90 +75,256 @@ pub fn fen_to_board(fen: &String) -> piece::Game {
     fen_white_to_index.insert('Q', 4);
     fen_white_to_index.insert('K', 5);
 
-    // Outer-most parts of FEN notation are separated by space
+    // Outer-most parts of FEN notation are separated by space. Each encoding
+    // keeps the column it starts at so errors can point at it.
     // Where pieces are
-    let mut pieces_encoding: String = String::new();
+    let mut pieces_encoding: (usize, &str) = (0, "");
     // Whose turn it is
-    let mut side_encoding: String = String::new();
+    let mut side_encoding: (usize, &str) = (0, "");
 
-    // The next 4 are not yet supported by this program, but are captured for future implementation
-    let mut castling_availability_encoding: String = String::new();
-    let mut en_passant_encoding: String = String::new();
-    let mut halfmove_clock_encoding: String = String::new();
-    let mut fullmove_clock_encoding: String = String::new();
+    // Castling availability, en passant square and the two clocks, defaulted when left out
+    let mut castling_availability_encoding: (usize, &str) = (0, "-");
+    let mut en_passant_encoding: (usize, &str) = (0, "-");
+    let mut halfmove_clock_encoding: (usize, &str) = (0, "0");
+    let mut fullmove_clock_encoding: (usize, &str) = (0, "1");
+    // Where a seventh field starts, if there is one
+    let mut extra_field = None;
 
     {
-        // Remove the last \n
-        let mut fen_copy = fen.clone();
-        if fen_copy.ends_with('\n') {
-            fen_copy.remove(fen_copy.len() - 1);
-        }
+        // Remove the trailing newline
+        let fen = fen.trim_end_matches(['\n', '\r']);
+        side_encoding.0 = fen.chars().count();
         // Put encodings into a vector so we can quickly populate them
-        let mut encoding_vec = vec![&mut pieces_encoding, &mut side_encoding, &mut castling_availability_encoding, &mut en_passant_encoding, &mut halfmove_clock_encoding, &mut fullmove_clock_encoding];
-        for (pos, encoding) in fen_copy.split(' ').enumerate() {
-            encoding_vec[pos].insert_str(0, encoding);
+        let mut encoding_vec = [&mut pieces_encoding, &mut side_encoding, &mut castling_availability_encoding, &mut en_passant_encoding, &mut halfmove_clock_encoding, &mut fullmove_clock_encoding];
+        let mut column = 0;
+        for (pos, encoding) in fen.split(' ').enumerate() {
+            match encoding_vec.get_mut(pos) {
+                // An empty field means two spaces in a row, point at the first one
+                Some(field) if encoding.is_empty() && column > 0 => **field = (column - 1, encoding),
+                Some(field) => **field = (column, encoding),
+                None => {
+                    extra_field.get_or_insert(column);
+                },
+            }
+            column += encoding.chars().count() + 1;
         }
     }
 
     // First one is piece positions
     {
-        let mut x: usize = 0;
-        let mut y: usize = 0;
-        for line in pieces_encoding.split('/') {
+        let mut column = pieces_encoding.0;
+        let mut ranks = 0;
+        for (x, line) in pieces_encoding.1.split('/').enumerate() {
+            ranks += 1;
+            if x >= 8 {
+                return Err(FenError::WrongNumberOfRanks { column: column - 1, found: pieces_encoding.1.split('/').count() });
+            }
+            let mut y: usize = 0;
             for input in line.chars() {
+                if y >= 8 {
+                    return Err(FenError::RankOverflow { column });
+                }
                 if fen_black_to_index.contains_key(&input) {
-                    board.push(piece::Piece {
+                    board[piece::square_index((x, y))] = Some(piece::Piece {
                         typ_index: fen_black_to_index[&input],
                         position: (x, y),
                         times_moved: 0, // we don't actually know the times moved...
                         side: piece::Side::Black,
-                        captured: false,
                     });
                     
                     // Move over to the right by one
                     y += 1;
                 } else if fen_white_to_index.contains_key(&input) {
-                    board.push(piece::Piece {
+                    board[piece::square_index((x, y))] = Some(piece::Piece {
                         typ_index: fen_white_to_index[&input],
                         position: (x, y),
                         times_moved: 0, // we don't actually know the times moved...
                         side: piece::Side::White,
-                        captured: false,
                     });
 
                     // Move over to the right by one
                     y += 1;
                 } else if FEN_SPACE.contains(&input) {
                     // Move over to the right by int(input)
-                    let z = (input.to_string()).parse::<usize>().unwrap();
+                    let z = input.to_digit(10).unwrap() as usize;
+                    if y + z > 8 {
+                        return Err(FenError::RankOverflow { column });
+                    }
                     y += z;
                 } else {
-                    panic!("Not valid fen: {}", input);
+                    return Err(FenError::InvalidPiece { column, found: input });
                 }
+                column += 1;
+            }
+            if y < 8 {
+                return Err(FenError::RankUnderflow { column });
             }
-            // Move to next line
-            x += 1;
-            y = 0;
+            // Skip over the '/'
+            column += 1;
+        }
+        if ranks != 8 {
+            return Err(FenError::WrongNumberOfRanks { column: column - 1, found: ranks });
         }
     }
 
-    {
-        // Grab the current player
-        if side_encoding == "w" {
-            side = piece::Side::White;
-        } else if side_encoding == "b" {
-            side = piece::Side::Black;
-        } else {
-            panic!("Invalid side");
+    // Grab the current player
+    let side = match side_encoding.1 {
+        "w" => piece::Side::White,
+        "b" => piece::Side::Black,
+        _ => return Err(FenError::InvalidSide { column: side_encoding.0 }),
+    };
+
+    // Either "-" or each of KQkq at most once, in that order
+    let mut castling = piece::CastlingRights::none();
+    if castling_availability_encoding.1 != "-" {
+        if castling_availability_encoding.1.is_empty() {
+            return Err(FenError::InvalidCastling { column: castling_availability_encoding.0 });
+        }
+        let mut remaining = &CASTLING_RIGHTS[..];
+        for (offset, right) in castling_availability_encoding.1.chars().enumerate() {
+            match remaining.iter().position(|other| *other == right) {
+                Some(index) => remaining = &remaining[index + 1..],
+                None => return Err(FenError::InvalidCastling { column: castling_availability_encoding.0 + offset }),
+            }
+            match right {
+                'K' => castling.white_kingside = true,
+                'Q' => castling.white_queenside = true,
+                'k' => castling.black_kingside = true,
+                _ => castling.black_queenside = true,
+            }
         }
     }
+    // A right is only kept while its king and rook are still at home
+    let at_home = |position: piece::Position, typ_index: usize, side: piece::Side| {
+        matches!(board[piece::square_index(position)], Some(piece) if piece.typ_index == typ_index && piece.side == side)
+    };
+    let white_king = at_home((7, 4), piece::KING_INDEX, piece::Side::White);
+    let black_king = at_home((0, 4), piece::KING_INDEX, piece::Side::Black);
+    castling.white_kingside &= white_king && at_home((7, 7), piece::ROOK_INDEX, piece::Side::White);
+    castling.white_queenside &= white_king && at_home((7, 0), piece::ROOK_INDEX, piece::Side::White);
+    castling.black_kingside &= black_king && at_home((0, 7), piece::ROOK_INDEX, piece::Side::Black);
+    castling.black_queenside &= black_king && at_home((0, 0), piece::ROOK_INDEX, piece::Side::Black);
 
-    return piece::Game {
-        pieces: board,
-        side: side,
+    // Either "-" or the square behind a pawn that just moved two squares
+    let en_passant = {
+        let mut chars = en_passant_encoding.1.chars();
+        match (chars.next(), chars.next(), chars.next()) {
+            (Some('-'), None, None) => None,
+            (Some(file @ 'a'..='h'), Some('3'), None) if side == piece::Side::Black => Some((5, file as usize - 'a' as usize)),
+            (Some(file @ 'a'..='h'), Some('6'), None) if side == piece::Side::White => Some((2, file as usize - 'a' as usize)),
+            _ => return Err(FenError::InvalidEnPassant { column: en_passant_encoding.0 }),
+        }
     };
+    // The pawn has to be in front of the square, with the square and the one
+    // it came from both empty
+    if let Some((row, col)) = en_passant {
+        let (pawn_row, from_row) = if side == piece::Side::White { (row + 1, row - 1) } else { (row - 1, row + 1) };
+        let pushed = matches!(board[piece::square_index((pawn_row, col))], Some(pawn) if pawn.typ_index == piece::PAWN_INDEX && pawn.side != side);
+        if !pushed || board[piece::square_index((row, col))].is_some() || board[piece::square_index((from_row, col))].is_some() {
+            return Err(FenError::InvalidEnPassant { column: en_passant_encoding.0 });
+        }
+    }
+
+    let halfmove_clock = match halfmove_clock_encoding.1.parse::<usize>() {
+        Ok(clock) => clock,
+        Err(_) => return Err(FenError::InvalidHalfmoveClock { column: halfmove_clock_encoding.0 }),
+    };
+    let fullmove_number = match fullmove_clock_encoding.1.parse::<usize>() {
+        Ok(number) if number > 0 => number,
+        _ => return Err(FenError::InvalidFullmoveNumber { column: fullmove_clock_encoding.0 }),
+    };
+    // Checked last, so a doubled space shows up as the empty field after it
+    // rather than as a field too many at the end
+    if let Some(column) = extra_field {
+        return Err(FenError::TooManyFields { column });
+    }
+
+    let mut game = piece::Game {
+        board,
+        bitboards: Bitboards::from_board(&board),
+        side,
+        castling,
+        en_passant,
+        halfmove_clock,
+        fullmove_number,
+        hash: 0,
+        position_history: vec![],
+    };
+    game.hash = zobrist::hash(&game);
+    game.position_history.push(game.hash);
+    Ok(game)
+}
+
+/// Writes the game as a six-field FEN string
+pub fn board_to_fen(game: &piece::Game) -> String {
+    // Piece placement, from rank 8 down to rank 1
+    let mut ranks: Vec<String> = vec![];
+    for x in 0..8 {
+        let mut rank = String::new();
+        let mut empty = 0;
+        for y in 0..8 {
+            match game.piece_at((x, y)) {
+                None => empty += 1,
+                Some(piece) => {
+                    if empty > 0 {
+                        rank.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    rank.push(piece_to_fen(piece));
+                }
+            }
+        }
+        if empty > 0 {
+            rank.push_str(&empty.to_string());
+        }
+        ranks.push(rank);
+    }
+
+    let side_encoding = if game.side == piece::Side::White { "w" } else { "b" };
+
+    let mut castling_availability_encoding = String::new();
+    for (right, allowed) in CASTLING_RIGHTS.iter().zip([
+        game.castling.white_kingside,
+        game.castling.white_queenside,
+        game.castling.black_kingside,
+        game.castling.black_queenside,
+    ]) {
+        if allowed {
+            castling_availability_encoding.push(*right);
+        }
+    }
+    if castling_availability_encoding.is_empty() {
+        castling_availability_encoding.push('-');
+    }
+
+    let en_passant_encoding = match game.en_passant {
+        Some(position) => position_to_square(position),
+        None => "-".to_string(),
+    };
+
+    format!(
+        "{} {} {} {} {} {}",
+        ranks.join("/"),
+        side_encoding,
+        castling_availability_encoding,
+        en_passant_encoding,
+        game.halfmove_clock,
+        game.fullmove_number,
+    )
+}
+
+const CASTLING_RIGHTS: [char; 4] = ['K', 'Q', 'k', 'q'];
+
+/// Algebraic name of a square, e.g. (7, 4) is "e1"
+pub fn position_to_square(position: piece::Position) -> String {
+    format!("{}{}", (b'a' + position.1 as u8) as char, 8 - position.0)
 }
 
-pub fn board_to_fen(board: &Vec<Piece>) -> &str {
-    panic!("Not yet implemented");
+fn piece_to_fen(piece: &Piece) -> char {
+    let letter = ['p', 'r', 'n', 'b', 'q', 'k'][piece.typ_index];
+    if piece.side == piece::Side::White {
+        letter.to_ascii_uppercase()
+    } else {
+        letter
+    }
 }
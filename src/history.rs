@@ -0,0 +1,89 @@
+//! The moves of a game as played at the board, so they can be taken back
+//! with undo and played again with redo.
+
+use crate::piece::{Game, Move, Side, Undo};
+use crate::san;
+
+/// The moves played in a game, most recent last, and the moves taken back
+/// that can still be replayed. It doesn't hold the game itself, every
+/// method is given the game the moves were played in.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    played: Vec<Undo>,
+    // Most recently undone last, so redo pops from the end
+    undone: Vec<Move>,
+}
+
+impl History {
+    pub fn new() -> History {
+        History::default()
+    }
+
+    /// Plays `movement`, which must be legal, and records it. Moves that
+    /// were undone can't be redone after this.
+    pub fn play(&mut self, game: &mut Game, movement: Move) {
+        self.played.push(game.make_move(movement));
+        self.undone.clear();
+    }
+
+    /// Takes back the last move played, None if there is none
+    pub fn undo(&mut self, game: &mut Game) -> Option<Move> {
+        let undo = self.played.pop()?;
+        game.unmake_move(undo);
+        self.undone.push(undo.movement);
+        Some(undo.movement)
+    }
+
+    /// Plays the last move taken back again, None if there is none
+    pub fn redo(&mut self, game: &mut Game) -> Option<Move> {
+        let movement = self.undone.pop()?;
+        self.played.push(game.make_move(movement));
+        Some(movement)
+    }
+
+    /// Whether no moves have been played
+    pub fn is_empty(&self) -> bool {
+        self.played.is_empty()
+    }
+
+    /// The moves played, in order
+    pub fn moves(&self) -> Vec<Move> {
+        self.played.iter().map(|undo| undo.movement).collect()
+    }
+
+    /// `game` as it was before any of the moves were played
+    pub fn start(&self, game: &Game) -> Game {
+        let mut start = game.clone();
+        for undo in self.played.iter().rev() {
+            start.unmake_move(*undo);
+        }
+        start
+    }
+
+    /// The moves in SAN, one numbered move pair per line like "1. e4 e5". A
+    /// first line for Black starts "N...".
+    pub fn lines(&self, game: &Game) -> Vec<String> {
+        let mut lines = vec![];
+        // SAN depends on the position before each move, so go back to the start and replay
+        let mut replay = self.start(game);
+        let mut line = String::new();
+        for undo in &self.played {
+            let movement = san::move_to_san(&replay, &undo.movement);
+            if replay.side == Side::White {
+                if !line.is_empty() {
+                    lines.push(line);
+                }
+                line = format!("{}. {}", replay.fullmove_number, movement);
+            } else if line.is_empty() {
+                line = format!("{}... {}", replay.fullmove_number, movement);
+            } else {
+                line = format!("{} {}", line, movement);
+            }
+            replay.make_move(undo.movement);
+        }
+        if !line.is_empty() {
+            lines.push(line);
+        }
+        lines
+    }
+}
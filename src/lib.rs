@@ -0,0 +1,43 @@
+//! Chess rules and game state.
+//!
+//! A [`Game`] is usually created from FEN with [`fen_to_board`] (use
+//! [`fen::START_FEN`] for a new game). [`Game::legal_moves`] lists the moves
+//! for the side to move, [`Game::move_piece`] plays one, and [`Game::status`]
+//! tells whether the game has ended. [`Game::make_move`] and
+//! [`Game::unmake_move`] play and take back moves without checking them,
+//! for search and replay. [`board_to_fen`] exports the position, and
+//! [`san_to_move`] and [`move_to_san`] read and write moves like `Nf3`.
+//!
+//! [`history::History`] keeps the moves of a game for undo and redo, and
+//! [`pgn::games`] reads the games of a PGN file.
+//!
+//! [`eval::evaluate`] scores a position, [`engine::search`] picks a move, remembering what it found in a
+//! [`transposition::TranspositionTable`], and [`uci::run`] puts it behind the UCI
+//! protocol for chess GUIs. [`polyglot::Book`] reads opening books in the
+//! Polyglot `.bin` format.
+//!
+//! [`Game::hash`] is a Zobrist key of the position, see [`zobrist`].
+//!
+//! [`perft`] counts the move tree to check the move generator against
+//! published numbers.
+//!
+//! Squares are `(row, column)` pairs where row 0 is rank 8 and column 0 is
+//! file a, so e2 is `(6, 4)`.
+
+pub mod bitboard;
+pub mod engine;
+pub mod eval;
+pub mod fen;
+pub mod history;
+pub mod perft;
+pub mod pgn;
+pub mod piece;
+pub mod polyglot;
+pub mod san;
+pub mod transposition;
+pub mod uci;
+pub mod zobrist;
+
+pub use fen::{board_to_fen, fen_to_board, FenError};
+pub use piece::{parse_movement, Board, CastlingRights, DrawReason, Game, GameStatus, Move, MoveError, MoveFlags, ParseMoveError, Piece, Position, Side, Undo};
+pub use san::{move_to_san, san_to_move, SanError};
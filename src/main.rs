@@ -1,32 +1,28 @@
-use std::{collections::HashMap, io::{self}};
+use std::{fs, io::{self}, sync::atomic::AtomicBool, time::{Duration, Instant}};
 use inline_colorization::*;
-mod piece;
-mod fen;
+use chess::{engine, eval, fen, history::History, perft, pgn, piece, polyglot, san, transposition, uci};
 
-fn print_board(pieces: &Vec<piece::Piece>) {
-    let piece_positions = vec![8, 7, 6, 5, 4, 3, 2, 1];
+// How long the computer thinks about each move with --play-as
+const ENGINE_TIME: Duration = Duration::from_secs(2);
+
+fn print_board(board: &piece::Board) {
+    let piece_positions = [8, 7, 6, 5, 4, 3, 2, 1];
     print!("{style_bold}");
-    for x in 0..8 {
-        print!("{:}  ", piece_positions[x]);
+    for (x, rank) in piece_positions.iter().enumerate() {
+        print!("{:}  ", rank);
         for y in 0..8 {
-            if x % 2 == 0 && y % 2 == 0 {
-                print!("{bg_white}");
-            } else if x % 2 == 1 && y % 2 == 1 {
+            if x % 2 == y % 2 {
                 print!("{bg_white}");
             }
-            match piece::position_to_piece(&pieces, (x, y)) {
+            match piece::position_to_piece(board, (x, y)) {
                 None => print!("{}{bg_reset}", piece::EMPTY),
-                Some(piece_index) => {
-                    if pieces[piece_index].captured {
-                        // If captured, don't display
-                        continue;
-                    }
-                    if pieces[piece_index].side == piece::Side::Black {
+                Some(piece) => {
+                    if piece.side == piece::Side::Black {
                         print!("{color_bright_red}");
                     } else {
                         print!("{color_bright_white}");
                     }
-                    print!("{}{color_reset}{bg_reset}", pieces[piece_index].typ());
+                    print!("{}{color_reset}{bg_reset}", piece.typ());
                 }
             }
         }
@@ -35,157 +31,395 @@ fn print_board(pieces: &Vec<piece::Piece>) {
     println!("   a     b     c     d     e     f     g     h");
 }
 
-fn move_piece(mut pieces: &mut Vec<piece::Piece>, requested_piece: (usize, usize), destination: (usize, usize), turn: piece::Side) -> bool {
-    fn move_piece_to_dest(piece_index: usize, pieces: &mut Vec<piece::Piece>, destination: (usize, usize)) {
-        pieces[piece_index].position = (destination.0, destination.1);
-        pieces[piece_index].times_moved += 1;
-    }
-
-    // Check that the requested destination position is somewhat correct
-    if destination.0 >= 8 || destination.1 >= 8 {
-        println!("Invalid destination: {:?}", destination);
-        return false;
+fn parse_fen() -> Option<piece::Game> {
+    loop {
+        let mut fen_buf = String::new();
+        // Nothing more to read means nobody is going to type a FEN, so start a fresh game
+        match io::stdin().read_line(&mut fen_buf) {
+            Ok(0) | Err(_) => return None,
+            Ok(_) => {},
+        }
+        if fen_buf.trim().is_empty() {
+            return None;
+        }
+        match fen::fen_to_board(fen_buf.trim()) {
+            Ok(game) => return Some(game),
+            Err(error) => println!("Not a valid FEN ({}), try again.", error),
+        }
     }
+}
 
-    let maybe_piece = piece::position_to_piece(&pieces, requested_piece);
-    // Could be EMPTY (None), or another piece
-    let maybe_piece_at_destination = piece::position_to_piece(&pieces, destination);
-    match maybe_piece {
+// "load <file> [number]" reads a game (the first unless a number is given) from a
+// PGN file
+fn load_pgn(arguments: &str) -> Option<pgn::PgnGame> {
+    let arguments: Vec<&str> = arguments.split_whitespace().collect();
+    let (path, number) = match arguments[..] {
+        [path] => (path, "1"),
+        [path, number] => (path, number),
+        _ => {
+            println!("Usage: load <file> [game number]");
+            return None;
+        },
+    };
+    let number = match number.parse::<usize>() {
+        Ok(number) if number > 0 => number,
+        _ => {
+            println!("Not a valid game number: {}", number);
+            return None;
+        },
+    };
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(error) => {
+            println!("Can't read {}: {}", path, error);
+            return None;
+        },
+    };
+    let pgn = match pgn::games(&text).nth(number - 1) {
+        Some(Ok(pgn)) => pgn,
+        Some(Err(error)) => {
+            println!("Can't load game {}: {}", number, error);
+            return None;
+        },
         None => {
-            println!("Invalid requested piece: ({}, {})", requested_piece.0, requested_piece.1);
-            return false;
+            println!("{} doesn't have a game {}", path, number);
+            return None;
         },
-        Some(piece_index) => {
-            // Evaluate the requested destination based on the piece type
-            if pieces[piece_index].side != turn {
-                println!("Not the turn for the requested piece. It is {:?} turn.", turn);
-                return false;
-            }
+    };
 
-            let allowed_positions = pieces[piece_index].valid_movements(&pieces);
-            println!("{:?}", allowed_positions);
-            for allowed_pos in allowed_positions {
-                if allowed_pos.0 == destination.0 && allowed_pos.1 == destination.1 {
-                    // Check if there's a enemy piece there
-                    match maybe_piece_at_destination {
-                        Some(other_piece_index) => {
-                            pieces[other_piece_index].captured = true;
-                        },
-                        None => {},
-                    }
-                    move_piece_to_dest(piece_index, &mut pieces, destination);
-                    // If pawn reaches the end, turns into a piece::QUEEN
-                    if pieces[piece_index].typ() == piece::PAWN && (pieces[piece_index].position.0 == 0 || pieces[piece_index].position.0 == 7) {
-                        pieces[piece_index].transform_typ(piece::QUEEN);           
-                    }
-                    return true;
-                }
-            }
+    println!(
+        "{} - {}, {}",
+        pgn.tag("White").unwrap_or("?"),
+        pgn.tag("Black").unwrap_or("?"),
+        pgn.tag("Event").unwrap_or("?"),
+    );
+    Some(pgn)
+}
+
+// "perft <depth>" prints the node count, "perft divide <depth>" the count after each move
+fn run_perft(game: &piece::Game, arguments: &str) {
+    let arguments: Vec<&str> = arguments.split_whitespace().collect();
+    let (divide, depth) = match arguments[..] {
+        [depth] => (false, depth),
+        ["divide", depth] => (true, depth),
+        _ => {
+            println!("Usage: perft [divide] <depth>");
+            return;
+        },
+    };
+    let depth = match depth.parse::<usize>() {
+        Ok(depth) => depth,
+        Err(_) => {
+            println!("Not a valid depth: {}", depth);
+            return;
+        },
+    };
+
+    let start = Instant::now();
+    let nodes = if divide {
+        let mut total = 0;
+        for (movement, nodes) in perft::divide(game, depth) {
+            println!("{}: {}", movement, nodes);
+            total += nodes;
         }
+        total
+    } else {
+        perft::perft(game, depth)
+    };
+    println!("Nodes searched: {} ({:?})", nodes, start.elapsed());
+}
+
+// "save <file>" writes the game so far as PGN, with the tags of the game it
+// was `loaded` from, and its result as long as no moves were played since.
+// `result` overrides the result worked out from the position, for draws that
+// were claimed. Returns whether the file was written.
+fn save_pgn(game: &piece::Game, history: &History, loaded: Option<&pgn::PgnGame>, result: Option<&str>, arguments: &str) -> bool {
+    let path = arguments.trim();
+    if path.is_empty() {
+        println!("Usage: save <file>");
+        return false;
+    }
+    let mut record = match loaded {
+        Some(loaded) => loaded.with_moves(history.moves()),
+        None => pgn::PgnGame::new(history.start(game), history.moves()),
+    };
+    if let Some(result) = result {
+        record.result = Some(result.to_string());
+    }
+    match fs::write(path, pgn::game_to_pgn(&record)) {
+        Ok(()) => {
+            println!("Saved the game to {}.", path);
+            true
+        },
+        Err(error) => {
+            println!("Can't write {}: {}", path, error);
+            false
+        },
     }
-    return false;
 }
 
-fn parse_movement() -> Option<Vec<usize>> {
-    let mut numbers = String::new();
-
-    let mut letters_to_numbers: HashMap<char, usize> = HashMap::new();
-    letters_to_numbers.insert('a', 0);
-    letters_to_numbers.insert('b', 1);
-    letters_to_numbers.insert('c', 2);
-    letters_to_numbers.insert('d', 3);
-    letters_to_numbers.insert('e', 4);
-    letters_to_numbers.insert('f', 5);
-    letters_to_numbers.insert('g', 6);
-    letters_to_numbers.insert('h', 7);
-
-    let mut numbers_to_numbers: HashMap<char, usize> = HashMap::new();
-    numbers_to_numbers.insert('1', 7);
-    numbers_to_numbers.insert('2', 6);
-    numbers_to_numbers.insert('3', 5);
-    numbers_to_numbers.insert('4', 4);
-    numbers_to_numbers.insert('5', 3);
-    numbers_to_numbers.insert('6', 2);
-    numbers_to_numbers.insert('7', 1);
-    numbers_to_numbers.insert('8', 0);
-
-
-    let mut result: Vec<usize> = vec![0, 0, 0, 0];
-    match io::stdin().read_line(&mut numbers).ok() {
-        None => return None,
-        Some(_) => {
-            for (pos, movement) in numbers.replace('\n', "").split(' ').enumerate() {
-                if movement.len() != 2 {
-                    return None;
-                }
+// The moves played so far in SAN, numbered like "1. e4 e5", one move pair per line
+fn print_history(game: &piece::Game, history: &History) {
+    if history.is_empty() {
+        println!("No moves have been played yet.");
+        return;
+    }
+    for line in history.lines(game) {
+        println!("{}", line);
+    }
+}
 
-                for (inp_pos, char) in movement.chars().enumerate() {
-                    match if inp_pos == 0 { letters_to_numbers.get(&char) } else { numbers_to_numbers.get(&char) } {
-                        None => return None,
-                        Some(numbered_value) => {
-                            if pos == 0 && inp_pos == 0 {
-                                result[1] = *numbered_value;
-                            } else if pos == 0 && inp_pos == 1 {
-                                result[0] = *numbered_value;
-                            } else if pos == 1 && inp_pos == 0 {
-                                result[3] = *numbered_value;
-                            } else if pos == 1 && inp_pos == 1 {
-                                result[2] = *numbered_value;
-                            }
-                        }
-                    }
-                }
-            }
-        }
+// Reads the Polyglot book given with --book
+fn load_book(path: &str) -> Option<polyglot::Book> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(error) => {
+            println!("Can't read {}: {}", path, error);
+            return None;
+        },
+    };
+    match polyglot::Book::from_bytes(&bytes) {
+        Ok(book) => Some(book),
+        Err(error) => {
+            println!("{} isn't a Polyglot book: {}", path, error);
+            None
+        },
     }
-    return Some(result);
 }
 
-fn parse_fen() -> Option<piece::Game> {
-    let mut fen_buf = String::new();
-    io::stdin().read_line(&mut fen_buf).ok();
-    if fen_buf == "\n" {
-        return None;
+// "book" lists the book moves for the position, with their share of the weight
+fn print_book_moves(game: &piece::Game, book: Option<&polyglot::Book>) {
+    let book = match book {
+        Some(book) => book,
+        None => {
+            println!("No book loaded, start with --book <file> to use one.");
+            return;
+        },
+    };
+    let moves = book.moves(game);
+    if moves.is_empty() {
+        println!("The position isn't in the book.");
+        return;
+    }
+    let total: u64 = moves.iter().map(|(_, weight)| *weight as u64).sum();
+    for (movement, weight) in &moves {
+        println!("{:<8} {:>6} {:>5.1}%", san::move_to_san(game, movement), weight, *weight as f64 * 100.0 / total.max(1) as f64);
+    }
+}
+
+// "eval" prints each term of the static evaluation
+fn print_evaluation(game: &piece::Game) {
+    let evaluation = eval::evaluation(game);
+    println!("Material:       {:>6}", evaluation.material);
+    println!("Piece squares:  {:>6}", evaluation.piece_squares);
+    println!("Pawn structure: {:>6}", evaluation.pawn_structure);
+    println!("Mobility:       {:>6}", evaluation.mobility);
+    println!("King safety:    {:>6}", evaluation.king_safety);
+    println!("Total:          {:>6} (centipawns for White, game phase {}/{})", evaluation.total(), evaluation.phase, eval::PHASE_TOTAL);
+}
+
+fn draw_reason_text(reason: &piece::DrawReason) -> &str {
+    match reason {
+        piece::DrawReason::Stalemate => "stalemate",
+        piece::DrawReason::InsufficientMaterial => "insufficient material",
+        piece::DrawReason::FiftyMoveRule => "the fifty-move rule",
+        piece::DrawReason::ThreefoldRepetition => "threefold repetition",
+        piece::DrawReason::SeventyFiveMoveRule => "the seventy-five-move rule",
+        piece::DrawReason::FivefoldRepetition => "fivefold repetition",
     }
-    return Some(fen::fen_to_board(&fen_buf));
 }
 
 fn main() {
+    let arguments: Vec<String> = std::env::args().collect();
+    // Let a chess GUI drive us instead of a person
+    if arguments.iter().any(|argument| argument == "--uci") {
+        uci::run(io::stdin().lock(), io::stdout());
+        return;
+    }
+    // "--play-as white" or "--play-as black" is the side the person plays, the computer gets the other one
+    let computer = match arguments.iter().position(|argument| argument == "--play-as") {
+        None => None,
+        Some(index) => match arguments.get(index + 1).map(String::as_str) {
+            Some("white") => Some(piece::Side::Black),
+            Some("black") => Some(piece::Side::White),
+            _ => {
+                println!("Usage: chess [--uci | --play-as white|black] [--book <file>]");
+                return;
+            },
+        },
+    };
+    // The computer plays from the book while it can, and "book" shows what it has
+    let book = match arguments.iter().position(|argument| argument == "--book") {
+        None => None,
+        Some(index) => match arguments.get(index + 1) {
+            Some(path) => match load_book(path) {
+                Some(book) => Some(book),
+                None => return,
+            },
+            None => {
+                println!("Usage: chess [--uci | --play-as white|black] [--book <file>]");
+                return;
+            },
+        },
+    };
+
     println!("Press RETURN to start a fresh game, or enter a FEN notated game to start the game from that state.");
 
-    let mut pieces = vec![];
-    let mut side = piece::Side::White;
+    let mut game = match parse_fen() {
+        None => fen::fen_to_board(fen::START_FEN).expect("start position is valid FEN"),
+        Some(game) => game,
+    };
+    // Moves played, and moves taken back with "undo" that "redo" can replay
+    let mut history = History::new();
+    // The game loaded with "load", whose tags and result "save" writes back
+    let mut loaded: Option<pgn::PgnGame> = None;
+    let mut claimed_draw = false;
+    // What the computer found thinking about earlier moves, which often helps with
+    // the next one. Only made when there is a computer, it takes a fair bit of memory.
+    let table = computer.map(|_| transposition::TranspositionTable::default());
+    loop {
+        match game.status() {
+            piece::GameStatus::Ongoing => {},
+            piece::GameStatus::Checkmate { winner } => {
+                print_board(&game.board);
+                println!("Checkmate, {:?} wins.", winner);
+                break;
+            },
+            piece::GameStatus::Draw { reason } => {
+                print_board(&game.board);
+                println!("Draw by {}.", draw_reason_text(&reason));
+                break;
+            },
+        }
+        println!("Move {}, {:?} turn.", game.fullmove_number, game.side);
+        if game.is_in_check(&game.side) {
+            println!("{:?} is in check!", game.side);
+        }
+        print_board(&game.board);
+        let claimable_draw = game.claimable_draw();
+        if let Some(reason) = &claimable_draw {
+            println!("{:?} can claim a draw by {}, enter \"draw\" to do so.", game.side, draw_reason_text(reason));
+        }
+        if computer == Some(game.side) {
+            if let Some(movement) = book.as_ref().and_then(|book| book.choose(&game, polyglot::Selection::Weighted)) {
+                println!("{:?} plays {} from the book.", game.side, san::move_to_san(&game, &movement));
+                history.play(&mut game, movement);
+                continue;
+            }
+            let limits = engine::SearchLimits { depth: None, time: Some(ENGINE_TIME) };
+            let table = table.as_ref().expect("the table is made when the computer plays");
+            if let Some(movement) = engine::search(&game, &limits, table, &AtomicBool::new(false), |_| {}) {
+                println!("{:?} plays {}.", game.side, san::move_to_san(&game, &movement));
+                history.play(&mut game, movement);
+            }
+            continue;
+        }
 
-    match parse_fen() {
-        None => {
-            let start_fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".into();
-            let game = fen::fen_to_board(&start_fen);
-            pieces = game.pieces;
-            side = game.side;
-        },
-        Some(game) => {
-            pieces = game.pieces;
-            side = game.side;
+        let mut line = String::new();
+        // End of input, nobody is left to play
+        match io::stdin().read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {},
+        }
+        if line.trim() == "draw" {
+            match claimable_draw {
+                Some(reason) => {
+                    println!("{:?} claims a draw by {}.", game.side, draw_reason_text(&reason));
+                    claimed_draw = true;
+                    break;
+                },
+                None => {
+                    println!("No draw can be claimed right now.");
+                    continue;
+                },
+            }
+        }
+        match line.trim() {
+            // Against the computer its reply goes too, otherwise it would just play it again
+            "undo" => {
+                match history.undo(&mut game) {
+                    Some(_) => while computer == Some(game.side) && history.undo(&mut game).is_some() {},
+                    None => println!("There is no move to undo."),
+                }
+                continue;
+            },
+            "redo" => {
+                match history.redo(&mut game) {
+                    Some(_) => while computer == Some(game.side) && history.redo(&mut game).is_some() {},
+                    None => println!("There is no move to redo."),
+                }
+                continue;
+            },
+            "history" => {
+                print_history(&game, &history);
+                continue;
+            },
+            "eval" => {
+                print_evaluation(&game);
+                continue;
+            },
+            "book" => {
+                print_book_moves(&game, book.as_ref());
+                continue;
+            },
+            _ => {},
+        }
+        if let Some(arguments) = line.trim().strip_prefix("load") {
+            // Played from its start so the moves can be undone, and kept for "save"
+            if let Some(record) = load_pgn(arguments) {
+                game = record.start.clone();
+                history = History::new();
+                for movement in &record.moves {
+                    history.play(&mut game, *movement);
+                }
+                loaded = Some(record);
+            }
+            continue;
+        }
+        if let Some(arguments) = line.trim().strip_prefix("save") {
+            save_pgn(&game, &history, loaded.as_ref(), None, arguments);
+            continue;
+        }
+        if let Some(arguments) = line.trim().strip_prefix("perft") {
+            run_perft(&game, arguments);
+            continue;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        // Squares like "e2e4" first, anything else has to be SAN like "Nf3". If
+        // it isn't either, the squares error says best what's wrong.
+        let movement = match piece::parse_movement(&line) {
+            Ok(movement) => game.legal_move(&movement).map_err(|error| error.to_string()),
+            Err(parse_error) => match san::san_to_move(&game, &line) {
+                Err(san::SanError::Invalid) => Err(parse_error.to_string()),
+                result => result.map_err(|error| error.to_string()),
+            },
+        };
+        // On success the turn passes to the other player, and anything
+        // that was undone can't be redone anymore
+        match movement {
+            Ok(movement) => history.play(&mut game, movement),
+            Err(error) => println!("Can't play that move: {}.", error),
         }
     }
 
-    let mut turn = side;
+    // One last chance to keep a record of the game
+    let result = if claimed_draw { Some("1/2-1/2") } else { None };
     loop {
-        println!("{:?} turn.", turn);
-        print_board(&pieces);
-        let maybe_movement = parse_movement();
-        match maybe_movement {
-            None => continue,
-            Some(movement) => {
-                if move_piece(&mut pieces, (movement[0], movement[1]), (movement[2], movement[3]), turn.clone()) == true {
-                    // Move was successful, switch turn to other player
-                    if turn == piece::Side::White {
-                        turn = piece::Side::Black;
-                    } else {
-                        turn = piece::Side::White;
-                    }
+        println!("Enter \"save <file>\" to save the game as PGN, or RETURN to quit.");
+        let mut line = String::new();
+        match io::stdin().read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {},
+        }
+        match line.trim().strip_prefix("save") {
+            Some(arguments) => {
+                if save_pgn(&game, &history, loaded.as_ref(), result, arguments) {
+                    break;
                 }
-            }
+            },
+            None => break,
         }
     }
-}
\ No newline at end of file
+}
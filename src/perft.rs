@@ -0,0 +1,47 @@
+//! Perft: counting the leaf nodes of the legal move tree to a fixed depth.
+//! The counts for well-known positions are published, so any difference
+//! points at a move generation bug.
+
+use crate::piece::{Game, Move};
+
+/// The number of move sequences of exactly `depth` plies from `game`
+pub fn perft(game: &Game, depth: usize) -> u64 {
+    count(&mut game.clone(), depth)
+}
+
+/// perft split by the first move, for narrowing down which move a wrong
+/// count comes from
+pub fn divide(game: &Game, depth: usize) -> Vec<(Move, u64)> {
+    if depth == 0 {
+        return vec![];
+    }
+    let mut game = game.clone();
+    game.legal_moves()
+        .into_iter()
+        .map(|movement| {
+            let undo = game.make_move(movement);
+            let nodes = count(&mut game, depth - 1);
+            game.unmake_move(undo);
+            (movement, nodes)
+        })
+        .collect()
+}
+
+// perft on a game that gets played forward and back again as we go
+fn count(game: &mut Game, depth: usize) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves = game.legal_moves();
+    if depth == 1 {
+        // Counting the moves is enough, no need to play them
+        return moves.len() as u64;
+    }
+    let mut nodes = 0;
+    for movement in moves {
+        let undo = game.make_move(movement);
+        nodes += count(game, depth - 1);
+        game.unmake_move(undo);
+    }
+    nodes
+}
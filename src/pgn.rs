@@ -0,0 +1,384 @@
+//! Portable Game Notation, the usual format for storing games. A PGN file
+//! holds any number of games, each a list of `[Tag "value"]` pairs followed
+//! by the moves in SAN. Comments, variations and annotations are skipped;
+//! only the main line is kept.
+//!
+//! [`games`] reads a file and [`game_to_pgn`] writes a game back out.
+
+use std::fmt;
+
+use crate::fen::{self, FenError};
+use crate::piece::{Game, GameStatus, Move, Side};
+use crate::san::{self, SanError};
+
+/// Why a game in a PGN file couldn't be read. Every variant carries the
+/// (1-based) line of the file it happened on.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum PgnError {
+    /// A tag pair isn't shaped like `[Name "value"]`
+    InvalidTag { line: usize },
+    /// A `{` comment is never closed
+    UnclosedComment { line: usize },
+    /// A `)` without a `(`, or a `(` that is never closed
+    UnbalancedVariation { line: usize },
+    /// The FEN tag isn't valid FEN
+    InvalidFen { line: usize, error: FenError },
+    /// A move that can't be played in the position it comes up in
+    IllegalMove { line: usize, san: String, error: SanError },
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PgnError::InvalidTag { line } => write!(f, "invalid tag pair on line {}", line),
+            PgnError::UnclosedComment { line } => write!(f, "comment starting on line {} is never closed", line),
+            PgnError::UnbalancedVariation { line } => write!(f, "unbalanced variation parentheses on line {}", line),
+            PgnError::InvalidFen { line, error } => write!(f, "invalid FEN tag on line {}: {}", line, error),
+            PgnError::IllegalMove { line, san, error } => write!(f, "can't play {} on line {}: {}", san, line, error),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+/// One game from a PGN file, with its moves checked by replaying them
+#[derive(Debug, Clone)]
+pub struct PgnGame {
+    /// The tag pairs in the order they were given
+    pub tags: Vec<(String, String)>,
+    /// The position before the first move, from the FEN tag if there is one
+    pub start: Game,
+    /// The main line, each one legal in the position it's played in
+    pub moves: Vec<Move>,
+    /// "1-0", "0-1", "1/2-1/2" or "*" when the movetext ends with one
+    pub result: Option<String>,
+}
+
+/// The Seven Tag Roster, the tags every PGN game has, in the order they're written
+pub const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+impl PgnGame {
+    /// A game played from `start`, with no tags yet. The result comes from
+    /// how the game stands after the moves: "*" if it hasn't ended.
+    pub fn new(start: Game, moves: Vec<Move>) -> PgnGame {
+        let mut game = PgnGame { tags: vec![], start, moves, result: None };
+        let result = match game.final_game().status() {
+            GameStatus::Ongoing => "*",
+            GameStatus::Checkmate { winner: Side::White } => "1-0",
+            GameStatus::Checkmate { winner: Side::Black } => "0-1",
+            GameStatus::Draw { .. } => "1/2-1/2",
+        };
+        game.result = Some(result.to_string());
+        game
+    }
+
+    /// This game with `moves` as its main line instead, keeping the tags and
+    /// start. The result stays too while the moves are the same, otherwise
+    /// it is worked out again as in new.
+    pub fn with_moves(&self, moves: Vec<Move>) -> PgnGame {
+        let result = match &self.result {
+            Some(result) if moves == self.moves => Some(result.clone()),
+            _ => PgnGame::new(self.start.clone(), moves.clone()).result,
+        };
+        PgnGame { tags: self.tags.clone(), start: self.start.clone(), moves, result }
+    }
+
+    /// The value of the tag called `name`, if the game has it
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+    }
+
+    /// The game after its first `ply` moves, None if it doesn't have that many
+    pub fn game_at(&self, ply: usize) -> Option<Game> {
+        let mut game = self.start.clone();
+        for movement in self.moves.get(..ply)? {
+            game.make_move(*movement);
+        }
+        Some(game)
+    }
+
+    /// The game after all of its moves
+    pub fn final_game(&self) -> Game {
+        self.game_at(self.moves.len()).expect("every move is in range")
+    }
+}
+
+/// Writes `game` as PGN: the Seven Tag Roster (with "?" for anything
+/// unknown), SetUp and FEN tags if it didn't start from the standard
+/// position, any other tags, and then the moves in SAN wrapped at 80 columns.
+pub fn game_to_pgn(game: &PgnGame) -> String {
+    let result = game.result.as_deref().unwrap_or("*");
+    let mut tags: Vec<(&str, String)> = SEVEN_TAG_ROSTER
+        .iter()
+        .map(|name| {
+            let value = match *name {
+                "Result" => result,
+                "Date" => game.tag(name).unwrap_or("????.??.??"),
+                _ => game.tag(name).unwrap_or("?"),
+            };
+            (*name, value.to_string())
+        })
+        .collect();
+    let start = fen::board_to_fen(&game.start);
+    if start != fen::START_FEN {
+        tags.push(("SetUp", "1".to_string()));
+        tags.push(("FEN", start));
+    }
+    for (name, value) in &game.tags {
+        if !SEVEN_TAG_ROSTER.contains(&name.as_str()) && name != "SetUp" && name != "FEN" {
+            tags.push((name, value.clone()));
+        }
+    }
+
+    let mut pgn = String::new();
+    for (name, value) in tags {
+        pgn.push_str(&format!("[{} \"{}\"]\n", name, value.replace('\\', "\\\\").replace('"', "\\\"")));
+    }
+    pgn.push('\n');
+
+    // Move numbers go before White's moves, and before the first move if Black starts
+    let mut words = vec![];
+    let mut position = game.start.clone();
+    for (ply, movement) in game.moves.iter().enumerate() {
+        if position.side == Side::White {
+            words.push(format!("{}.", position.fullmove_number));
+        } else if ply == 0 {
+            words.push(format!("{}...", position.fullmove_number));
+        }
+        words.push(san::move_to_san(&position, movement));
+        position.make_move(*movement);
+    }
+    words.push(result.to_string());
+
+    let mut line = String::new();
+    for word in words {
+        if !line.is_empty() && line.len() + 1 + word.len() > 80 {
+            pgn.push_str(&line);
+            pgn.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&word);
+    }
+    pgn.push_str(&line);
+    pgn.push('\n');
+    pgn
+}
+
+/// Every game in `text`, in order. A game that can't be read comes out as
+/// an error, and the games after it are still read.
+pub fn games(text: &str) -> Games<'_> {
+    Games { chars: text.chars().peekable(), line: 1 }
+}
+
+/// Iterator over the games of a PGN file, see games
+pub struct Games<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+}
+
+// The parts of a PGN file we care about
+enum Token {
+    Tag(String, String),
+    Move(String),
+    Result(String),
+    VariationStart,
+    VariationEnd,
+}
+
+impl Iterator for Games<'_> {
+    type Item = Result<PgnGame, PgnError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut tags = vec![];
+        let mut fen_line = 0;
+        let mut moves: Vec<(usize, String)> = vec![];
+        let mut result = None;
+        let mut error = None;
+        let mut variation_depth = 0;
+        let mut variation_line = 0;
+
+        loop {
+            self.skip_whitespace();
+            // Tags after moves belong to the next game
+            if self.chars.peek() == Some(&'[') && !moves.is_empty() {
+                break;
+            }
+            let line = self.line;
+            let token = match self.token() {
+                None => break,
+                Some(Ok(token)) => token,
+                Some(Err(token_error)) => {
+                    error.get_or_insert(token_error);
+                    continue;
+                },
+            };
+            match token {
+                Token::Tag(name, value) => {
+                    if name == "FEN" {
+                        fen_line = line;
+                    }
+                    tags.push((name, value));
+                },
+                // Only the main line is played, everything in a variation is skipped
+                Token::VariationStart => {
+                    if variation_depth == 0 {
+                        variation_line = line;
+                    }
+                    variation_depth += 1;
+                },
+                Token::VariationEnd => {
+                    if variation_depth == 0 {
+                        error.get_or_insert(PgnError::UnbalancedVariation { line });
+                    } else {
+                        variation_depth -= 1;
+                    }
+                },
+                Token::Move(san) => {
+                    if variation_depth == 0 {
+                        moves.push((line, san));
+                    }
+                },
+                Token::Result(found) => {
+                    if variation_depth == 0 {
+                        result = Some(found);
+                        break;
+                    }
+                },
+            }
+        }
+        if tags.is_empty() && moves.is_empty() && result.is_none() && error.is_none() {
+            return None;
+        }
+        if variation_depth > 0 {
+            error.get_or_insert(PgnError::UnbalancedVariation { line: variation_line });
+        }
+        if let Some(error) = error {
+            return Some(Err(error));
+        }
+        Some(replay(tags, fen_line, moves, result))
+    }
+}
+
+impl Games<'_> {
+    fn bump(&mut self) -> Option<char> {
+        let next = self.chars.next();
+        if next == Some('\n') {
+            self.line += 1;
+        }
+        next
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.peek().is_some_and(|next| next.is_whitespace()) {
+            self.bump();
+        }
+    }
+
+    // The next token worth keeping. Comments, move numbers and annotations are
+    // skipped over. None at the end of the text.
+    fn token(&mut self) -> Option<Result<Token, PgnError>> {
+        loop {
+            self.skip_whitespace();
+            let line = self.line;
+            match self.bump()? {
+                '[' => return Some(self.tag(line)),
+                '{' => {
+                    loop {
+                        match self.bump() {
+                            Some('}') => break,
+                            Some(_) => {},
+                            None => return Some(Err(PgnError::UnclosedComment { line })),
+                        }
+                    }
+                },
+                // Rest-of-line comment
+                ';' => while self.bump().is_some_and(|next| next != '\n') {},
+                '(' => return Some(Ok(Token::VariationStart)),
+                ')' => return Some(Ok(Token::VariationEnd)),
+                first => {
+                    let mut word = first.to_string();
+                    while let Some(next) = self.chars.peek() {
+                        if next.is_whitespace() || "[]{}();".contains(*next) {
+                            break;
+                        }
+                        word.push(*next);
+                        self.bump();
+                    }
+                    if ["1-0", "0-1", "1/2-1/2", "*"].contains(&word.as_str()) {
+                        return Some(Ok(Token::Result(word)));
+                    }
+                    // NAGs like $1 and bare annotations like !?
+                    if word.starts_with('$') || word.chars().all(|c| c == '!' || c == '?') {
+                        continue;
+                    }
+                    // Move numbers, "12." or "12...", possibly stuck to the move ("12.e4")
+                    if word.starts_with(|c: char| c.is_ascii_digit()) && word.contains('.') {
+                        word = word.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.').to_string();
+                        if word.is_empty() {
+                            continue;
+                        }
+                    }
+                    return Some(Ok(Token::Move(word)));
+                },
+            }
+        }
+    }
+
+    // A tag pair, after its opening '['
+    fn tag(&mut self, line: usize) -> Result<Token, PgnError> {
+        let mut name = String::new();
+        while let Some(next) = self.chars.peek() {
+            if next.is_whitespace() {
+                break;
+            }
+            name.push(*next);
+            self.bump();
+        }
+        self.skip_whitespace();
+        if name.is_empty() || self.bump() != Some('"') {
+            self.skip_line();
+            return Err(PgnError::InvalidTag { line });
+        }
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => break,
+                // Quotes and backslashes inside the value are escaped with a backslash
+                Some('\\') => value.extend(self.bump()),
+                Some('\n') | None => return Err(PgnError::InvalidTag { line }),
+                Some(next) => value.push(next),
+            }
+        }
+        self.skip_whitespace();
+        if self.bump() != Some(']') {
+            self.skip_line();
+            return Err(PgnError::InvalidTag { line });
+        }
+        Ok(Token::Tag(name, value))
+    }
+
+    fn skip_line(&mut self) {
+        while self.bump().is_some_and(|next| next != '\n') {}
+    }
+}
+
+// Plays the moves of a game through from its start position to check them
+fn replay(tags: Vec<(String, String)>, fen_line: usize, moves: Vec<(usize, String)>, result: Option<String>) -> Result<PgnGame, PgnError> {
+    let fen = tags.iter().find(|(name, _)| name == "FEN").map_or(fen::START_FEN, |(_, value)| value.as_str());
+    let start = fen::fen_to_board(fen).map_err(|error| PgnError::InvalidFen { line: fen_line, error })?;
+    let mut game = start.clone();
+    let mut played = vec![];
+    for (line, written) in moves {
+        match san::san_to_move(&game, &written) {
+            Ok(movement) => {
+                game.make_move(movement);
+                played.push(movement);
+            },
+            Err(error) => return Err(PgnError::IllegalMove { line, san: written, error }),
+        }
+    }
+    Ok(PgnGame { tags, start, moves: played, result })
+}
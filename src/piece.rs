@@ -1,3 +1,11 @@
+use std::fmt;
+
+use crate::bitboard::{self, Bitboard, Bitboards};
+use crate::fen;
+use crate::zobrist;
+
+// Piece type names, padded to the same width for printing. A piece's
+// typ_index is its index into PIECE_NAMES.
 pub const PAWN: &str = "pawn  ";
 pub const ROOK: &str = "rook  ";    
 pub const KNIGHT: &str = "knight";
@@ -6,388 +14,794 @@ pub const QUEEN: &str = "queen ";
 pub const KING: &str = "king  ";
 pub const EMPTY: &str = "      ";
 
+/// Every piece type, indexed by `Piece::typ_index`
 pub const PIECE_NAMES: [&str; 6] = [PAWN, ROOK, KNIGHT, BISHOP, QUEEN, KING];
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+// The typ_index of each piece type
+pub const PAWN_INDEX: usize = 0;
+pub const ROOK_INDEX: usize = 1;
+pub const KNIGHT_INDEX: usize = 2;
+pub const BISHOP_INDEX: usize = 3;
+pub const QUEEN_INDEX: usize = 4;
+pub const KING_INDEX: usize = 5;
+
+/// (row, column), where row 0 is rank 8 and column 0 is file a
+pub type Position = (usize, usize);
+
+/// The two players
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Side { White, Black }
 
-pub fn position_to_piece(pieces: &Vec<Piece>, position: (usize, usize)) -> Option<usize> {
-    // Discovers the piece at the requested position. Returns the address in pieces, if exists.
-    // Starting with O(N), I should probably make this O(1) at some point...    
-    for piece_index in 0..pieces.len() {
-        if pieces[piece_index].captured {
-            // Doesn't count as being in a position
-            continue;
-        }
-        if pieces[piece_index].position.0 == position.0 && pieces[piece_index].position.1 == position.1 {
-            return Some(piece_index);
+impl Side {
+    pub fn opponent(&self) -> Side {
+        match self {
+            Side::White => Side::Black,
+            Side::Black => Side::White,
         }
     }
-    return None;
 }
 
-#[derive(Debug)]
+/// The squares of the board, indexed by square_index
+pub type Board = [Option<Piece>; 64];
+
+/// Where `position` lives in a Board
+pub fn square_index(position: Position) -> usize {
+    position.0 * 8 + position.1
+}
+
+/// Discovers the piece at the requested position, if there is one
+pub fn position_to_piece(board: &Board, position: Position) -> Option<&Piece> {
+    board[square_index(position)].as_ref()
+}
+
+/// Whether any piece of `by` could capture on `position`. Pawns attack
+/// diagonally whether or not anything is standing there.
+pub fn is_attacked(bitboards: &Bitboards, position: Position, by: &Side) -> bool {
+    bitboards.is_attacked(square_index(position), by)
+}
+
+fn king_in_check(bitboards: &Bitboards, side: &Side) -> bool {
+    match bitboards.king_square(side) {
+        Some(king) => bitboards.is_attacked(king, &side.opponent()),
+        // Nothing to attack (e.g. a test position without kings)
+        None => false,
+    }
+}
+
+/// A piece on the board
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Piece {
+    /// Index into PIECE_NAMES
     pub typ_index: usize,
+    /// Always the square the piece is stored at in the Board
     pub position: (usize, usize),
     pub times_moved: usize,
     pub side: Side,
-    pub captured: bool,
 }
 
+/// A move as requested by a player or engine. `promotion` is the typ_index a
+/// pawn turns into when it reaches the last rank, a queen if left out.
+///
+/// The flags are only filled in for moves that come from the Game
+/// (Game::legal_moves, Game::legal_move). A requested move just needs the
+/// squares and the promotion.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Move {
+    pub from: Position,
+    pub to: Position,
+    pub promotion: Option<usize>,
+    pub flags: MoveFlags,
+}
+
+/// What kind of move a Move is, beyond going from one square to another
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct MoveFlags {
+    /// Takes a piece, including en passant
+    pub capture: bool,
+    /// The king moving two squares, the rook comes along
+    pub castle: bool,
+    /// A pawn taking the pawn that just double pushed past it
+    pub en_passant: bool,
+    /// A pawn moving two squares from its starting rank
+    pub double_push: bool,
+}
+
+impl Move {
+    /// A move with no flags set, e.g. as typed in by a player
+    pub fn new(from: Position, to: Position, promotion: Option<usize>) -> Move {
+        Move { from, to, promotion, flags: MoveFlags::default() }
+    }
+}
+
+/// Long algebraic notation, e.g. "e2e4" or "e7e8q"
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", fen::position_to_square(self.from), fen::position_to_square(self.to))?;
+        if let Some(promotion) = self.promotion {
+            write!(f, "{}", ['p', 'r', 'n', 'b', 'q', 'k'][promotion])?;
+        }
+        Ok(())
+    }
+}
+
+/// Why parse_movement couldn't read a move
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ParseMoveError {
+    /// Not two squares plus an optional promotion letter
+    WrongLength { found: usize },
+    /// A square's file isn't a to h
+    InvalidFile { found: char },
+    /// A square's rank isn't 1 to 8
+    InvalidRank { found: char },
+    /// The promotion letter isn't one of r, n, b or q
+    InvalidPromotion { found: char },
+}
+
+impl fmt::Display for ParseMoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseMoveError::WrongLength { found } => write!(f, "expected two squares and maybe a promotion piece, like e2e4 or e7e8q, but got {} characters", found),
+            ParseMoveError::InvalidFile { found } => write!(f, "'{}' is not a file, files go from a to h", found),
+            ParseMoveError::InvalidRank { found } => write!(f, "'{}' is not a rank, ranks go from 1 to 8", found),
+            ParseMoveError::InvalidPromotion { found } => write!(f, "'{}' is not a promotion piece, use r, n, b or q", found),
+        }
+    }
+}
+
+impl std::error::Error for ParseMoveError {}
+
+/// Reads a move in long algebraic notation, as used by UCI: "e2e4", "e7e8q",
+/// or "e1g1" for castling. The squares may also be separated by a space
+/// ("e2 e4"), and case and surrounding whitespace don't matter.
+pub fn parse_movement(text: &str) -> Result<Move, ParseMoveError> {
+    let movement: String = text.trim().to_lowercase().split(' ').collect();
+    let chars: Vec<char> = movement.chars().collect();
+    if chars.len() != 4 && chars.len() != 5 {
+        return Err(ParseMoveError::WrongLength { found: chars.len() });
+    }
+
+    let mut squares = [(0, 0); 2];
+    for (square, name) in squares.iter_mut().zip(chars[..4].chunks(2)) {
+        let column = match name[0] {
+            file @ 'a'..='h' => file as usize - 'a' as usize,
+            found => return Err(ParseMoveError::InvalidFile { found }),
+        };
+        let row = match name[1] {
+            rank @ '1'..='8' => '8' as usize - rank as usize,
+            found => return Err(ParseMoveError::InvalidRank { found }),
+        };
+        *square = (row, column);
+    }
+    let promotion = match chars.get(4) {
+        None => None,
+        Some('r') => Some(ROOK_INDEX),
+        Some('n') => Some(KNIGHT_INDEX),
+        Some('b') => Some(BISHOP_INDEX),
+        Some('q') => Some(QUEEN_INDEX),
+        Some(found) => return Err(ParseMoveError::InvalidPromotion { found: *found }),
+    };
+    Ok(Move::new(squares[0], squares[1], promotion))
+}
+
+/// Which castling moves are still allowed, as in the FEN castling field
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct CastlingRights {
+    pub white_kingside: bool,
+    pub white_queenside: bool,
+    pub black_kingside: bool,
+    pub black_queenside: bool,
+}
+
+impl CastlingRights {
+    pub fn none() -> CastlingRights {
+        CastlingRights {
+            white_kingside: false,
+            white_queenside: false,
+            black_kingside: false,
+            black_queenside: false,
+        }
+    }
+
+    pub fn revoke_side(&mut self, side: &Side) {
+        match side {
+            Side::White => {
+                self.white_kingside = false;
+                self.white_queenside = false;
+            },
+            Side::Black => {
+                self.black_kingside = false;
+                self.black_queenside = false;
+            },
+        }
+    }
+
+    /// Anything moving from or to a rook's home square means that rook can no
+    /// longer castle (it either moved or got captured)
+    pub fn revoke_square(&mut self, position: Position) {
+        match position {
+            (7, 7) => self.white_kingside = false,
+            (7, 0) => self.white_queenside = false,
+            (0, 7) => self.black_kingside = false,
+            (0, 0) => self.black_queenside = false,
+            _ => {},
+        }
+    }
+}
+
+/// Why a game was drawn
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum DrawReason {
+    /// The side to move has no legal moves and isn't in check
+    Stalemate,
+    /// No checkmate is possible with the remaining pieces
+    InsufficientMaterial,
+    /// Claimable: 50 moves each without a capture or pawn move
+    FiftyMoveRule,
+    /// Claimable: the same position occurred three times
+    ThreefoldRepetition,
+    /// Automatic: 75 moves each without a capture or pawn move
+    SeventyFiveMoveRule,
+    /// Automatic: the same position occurred five times
+    FivefoldRepetition,
+}
+
+/// Why Game::move_piece refused a move
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum MoveError {
+    /// A square is outside the board
+    OffBoard,
+    /// There is no piece on the starting square
+    NoPiece,
+    /// The piece belongs to the side not on move
+    NotYourTurn,
+    /// A promotion piece was given for a move that doesn't promote
+    UnexpectedPromotion,
+    /// Pawns can only promote to a rook, knight, bishop or queen
+    InvalidPromotion,
+    /// The piece can't move there, or doing so would leave the king in check
+    Illegal,
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveError::OffBoard => write!(f, "that square is not on the board"),
+            MoveError::NoPiece => write!(f, "there is no piece on that square"),
+            MoveError::NotYourTurn => write!(f, "that piece belongs to the other side"),
+            MoveError::UnexpectedPromotion => write!(f, "only a pawn reaching the last rank can promote"),
+            MoveError::InvalidPromotion => write!(f, "pawns can only promote to a rook, knight, bishop or queen"),
+            MoveError::Illegal => write!(f, "that move is not allowed"),
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+/// Where the game stands for the side to move
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum GameStatus {
+    Ongoing,
+    Checkmate { winner: Side },
+    Draw { reason: DrawReason },
+}
+
+/// Everything Game::unmake_move needs to take back a move played with
+/// Game::make_move
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Undo {
+    pub movement: Move,
+    /// The piece the move took, with the position it was taken on
+    pub captured: Option<Piece>,
+    pub castling: CastlingRights,
+    pub en_passant: Option<Position>,
+    pub halfmove_clock: usize,
+    pub fullmove_number: usize,
+}
+
+/// The full state of a game: the pieces plus everything FEN records, and the
+/// history needed for repetition draws
+#[derive(Debug, Clone)]
 pub struct Game {
-    pub pieces: Vec<Piece>,
+    pub board: Board,
+    /// The same pieces as `board`, as bitboards. Both are always updated together.
+    pub bitboards: Bitboards,
+    /// The side to move
     pub side: Side,
+    pub castling: CastlingRights,
+    /// The square a pawn skipped over with its double push last ply, if any
+    pub en_passant: Option<Position>,
+    /// Plies since the last capture or pawn move, for the fifty-move rule
+    pub halfmove_clock: usize,
+    /// Starts at 1 and goes up after every Black move
+    pub fullmove_number: usize,
+    /// Zobrist key of the position, kept up to date as pieces and moves change
+    pub hash: u64,
+    /// hash of every position reached so far, including the current one
+    pub position_history: Vec<u64>,
 }
 
-impl Piece {
-    pub fn typ(&self) -> &str {
-        return PIECE_NAMES[self.typ_index];
+impl Game {
+    // Updates castling rights, en passant, the clocks, the side to move, the
+    // hash and the position history after the piece now at `movement.to` was
+    // moved there. make_move has already taken the old state out of the hash.
+    // `is_pawn` is whether a pawn made the move, since it may have promoted since.
+    fn finish_move(&mut self, movement: &Move, is_pawn: bool) {
+        if self.piece_at(movement.to).expect("a piece was just moved here").typ() == KING {
+            self.castling.revoke_side(&self.side);
+        }
+        self.castling.revoke_square(movement.from);
+        self.castling.revoke_square(movement.to);
+
+        self.en_passant = if movement.flags.double_push {
+            Some(((movement.from.0 + movement.to.0) / 2, movement.from.1))
+        } else {
+            None
+        };
+
+        if is_pawn || movement.flags.capture {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if self.side == Side::Black {
+            self.fullmove_number += 1;
+        }
+        self.side = self.side.opponent();
+        self.hash ^= zobrist::state_key(self);
+        debug_assert_eq!(self.hash, zobrist::hash(self), "hash out of step after {}", movement);
+        self.position_history.push(self.hash);
     }
 
-    pub fn transform_typ(&mut self, new_typ: &str) {
-        for i in 0..PIECE_NAMES.len() {
-            if PIECE_NAMES[i] == new_typ {
-                self.typ_index = i;
-                return;
+    /// Checks a requested move (only the squares and promotion matter) and
+    /// returns the matching legal move with its flags filled in, and the
+    /// promotion piece set if it was left out
+    pub fn legal_move(&self, movement: &Move) -> Result<Move, MoveError> {
+        let requested_piece = movement.from;
+        let destination = movement.to;
+
+        // Check that the requested positions are somewhat correct
+        if requested_piece.0 >= 8 || requested_piece.1 >= 8 || destination.0 >= 8 || destination.1 >= 8 {
+            return Err(MoveError::OffBoard);
+        }
+
+        let piece = match self.piece_at(requested_piece) {
+            None => return Err(MoveError::NoPiece),
+            Some(piece) => piece,
+        };
+        if piece.side != self.side {
+            return Err(MoveError::NotYourTurn);
+        }
+
+        // Only a pawn reaching the last rank gets to pick a piece, and only one of these
+        let promoting = piece.typ() == PAWN && (destination.0 == 0 || destination.0 == 7);
+        if let Some(promotion) = movement.promotion {
+            if !promoting {
+                return Err(MoveError::UnexpectedPromotion);
+            }
+            if ![ROOK, KNIGHT, BISHOP, QUEEN].contains(&PIECE_NAMES.get(promotion).copied().unwrap_or(EMPTY)) {
+                return Err(MoveError::InvalidPromotion);
             }
         }
-        panic!("Invalid piece type change: {}", new_typ);
+
+        if self.legal_targets(requested_piece) & bitboard::bit(destination) == 0 {
+            return Err(MoveError::Illegal);
+        }
+
+        // If pawn reaches the end, turns into the requested piece, a QUEEN unless told otherwise
+        let promotion = if promoting { Some(movement.promotion.unwrap_or(QUEEN_INDEX)) } else { None };
+        Ok(Move { from: requested_piece, to: destination, promotion, flags: self.move_flags(piece, destination) })
     }
 
-    pub fn valid_movements(&self, pieces: &Vec<Piece>) -> Vec<(usize, usize)> {
-        // These are helpers which just check if a particular directional movement
-        // is on the board
-        fn move_up(amount: usize, piece: (usize, usize)) -> Option<(usize, usize)> {
-            if amount > piece.0 {
-                // Can't move up that much
-                return None;
-            }
-            return Some((piece.0 - amount, piece.1));
+    // The flags for moving `piece` to `destination`, which must be legal
+    fn move_flags(&self, piece: &Piece, destination: Position) -> MoveFlags {
+        let en_passant = piece.typ_index == PAWN_INDEX && Some(destination) == self.en_passant;
+        MoveFlags {
+            capture: en_passant || self.piece_at(destination).is_some(),
+            castle: piece.typ_index == KING_INDEX && piece.position.1.abs_diff(destination.1) == 2,
+            en_passant,
+            double_push: piece.typ_index == PAWN_INDEX && piece.position.0.abs_diff(destination.0) == 2,
         }
-        fn move_down(amount: usize, piece: (usize, usize)) -> Option<(usize, usize)> {
-            if amount + piece.0 >= 8 {
-                // Can't move down that much
-                return None;
-            }
-            return Some((piece.0 + amount, piece.1));
+    }
+
+    /// Plays `movement` for the side to move if it is legal, updating the
+    /// board and all of the game state. On error nothing is changed.
+    pub fn move_piece(&mut self, movement: &Move) -> Result<(), MoveError> {
+        let movement = self.legal_move(movement)?;
+        self.make_move(movement);
+        Ok(())
+    }
+
+    /// Plays a move from legal_moves or legal_move without checking it, and
+    /// returns what unmake_move needs to take it back
+    pub fn make_move(&mut self, movement: Move) -> Undo {
+        let undo = Undo {
+            movement,
+            captured: None,
+            castling: self.castling,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+        };
+        let is_pawn = self.piece_at(movement.from).is_some_and(|piece| piece.typ_index == PAWN_INDEX);
+        // Piece moves update the hash as they go, the rest changes all at once in finish_move
+        self.hash ^= zobrist::state_key(self);
+
+        // For en passant the captured pawn is beside the destination, not on it
+        let captured = if movement.flags.en_passant {
+            self.remove_piece((movement.from.0, movement.to.1))
+        } else {
+            self.remove_piece(movement.to)
+        };
+        self.move_on_board(movement.from, movement.to);
+        // Castling is a two column king move, the rook jumps to the other side of the king
+        if movement.flags.castle {
+            let (rook_from, rook_to) = castling_rook_columns(movement.to);
+            self.move_on_board((movement.to.0, rook_from), (movement.to.0, rook_to));
         }
-        fn move_left(amount: usize, piece: (usize, usize)) -> Option<(usize, usize)> {
-            if amount > piece.1 {
-                // Can't move left that much
-                return None;
+        if let Some(promotion) = movement.promotion {
+            if let Some(mut promoted) = self.remove_piece(movement.to) {
+                promoted.typ_index = promotion;
+                self.place_piece(promoted);
             }
-            return Some((piece.0, piece.1 - amount));
         }
-        fn move_right(amount: usize, piece: (usize, usize)) -> Option<(usize, usize)> {
-            if amount + piece.1 >= 8 {
-                // Can't move right that much
-                return None;
+        self.finish_move(&movement, is_pawn);
+        Undo { captured, ..undo }
+    }
+
+    /// Takes back the last move played with make_move, restoring the game to
+    /// exactly how it was before
+    pub fn unmake_move(&mut self, undo: Undo) {
+        let movement = undo.movement;
+        self.position_history.pop();
+        self.hash ^= zobrist::state_key(self);
+        self.side = self.side.opponent();
+        self.castling = undo.castling;
+        self.en_passant = undo.en_passant;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.fullmove_number = undo.fullmove_number;
+
+        self.move_back(movement.to, movement.from);
+        if movement.promotion.is_some() {
+            if let Some(mut pawn) = self.remove_piece(movement.from) {
+                pawn.typ_index = PAWN_INDEX;
+                self.place_piece(pawn);
             }
-            return Some((piece.0, piece.1 + amount));
         }
-        fn move_diagonal_up_left(amount: usize, piece: (usize, usize)) -> Option<(usize, usize)> {
-            if amount > piece.0 {
-                // Can't move up that much
-                return None;
-            }
-            if amount > piece.1 {
-                // Can't move left that much
-                return None;
-            }
-            return Some((piece.0 - amount, piece.1 - amount));
+        if movement.flags.castle {
+            let (rook_from, rook_to) = castling_rook_columns(movement.to);
+            self.move_back((movement.to.0, rook_to), (movement.to.0, rook_from));
         }
-        fn move_diagonal_up_right(amount: usize, piece: (usize, usize)) -> Option<(usize, usize)> {
-            if amount > piece.0 {
-                // Can't move up that much
-                return None;
-            }
-            if amount + piece.1 >= 8 {
-                // Can't move right that much
-                return None;
+        if let Some(captured) = undo.captured {
+            self.place_piece(captured);
+        }
+        // Whether en passant is possible depends on the pieces, so this goes last
+        self.hash ^= zobrist::state_key(self);
+        debug_assert_eq!(self.hash, zobrist::hash(self), "hash out of step after taking back {}", movement);
+    }
+
+    /// Takes the piece on `position` off the board
+    pub fn remove_piece(&mut self, position: Position) -> Option<Piece> {
+        let piece = self.board[square_index(position)].take()?;
+        self.bitboards.remove(&piece.side, piece.typ_index, square_index(position));
+        self.hash ^= zobrist::piece_key(&piece.side, piece.typ_index, square_index(position));
+        Some(piece)
+    }
+
+    /// Puts `piece` on the board at its position, replacing anything there
+    pub fn place_piece(&mut self, piece: Piece) {
+        self.remove_piece(piece.position);
+        self.bitboards.put(&piece.side, piece.typ_index, square_index(piece.position));
+        self.hash ^= zobrist::piece_key(&piece.side, piece.typ_index, square_index(piece.position));
+        self.board[square_index(piece.position)] = Some(piece);
+    }
+
+    // Moves whatever is on `from` to `to`, replacing anything that was there
+    fn move_on_board(&mut self, from: Position, to: Position) {
+        if let Some(mut piece) = self.remove_piece(from) {
+            piece.position = to;
+            piece.times_moved += 1;
+            self.place_piece(piece);
+        }
+    }
+
+    // move_on_board in reverse, for unmake_move
+    fn move_back(&mut self, from: Position, to: Position) {
+        if let Some(mut piece) = self.remove_piece(from) {
+            piece.position = to;
+            piece.times_moved -= 1;
+            self.place_piece(piece);
+        }
+    }
+
+    /// The piece standing on `position`, if any
+    pub fn piece_at(&self, position: Position) -> Option<&Piece> {
+        position_to_piece(&self.board, position)
+    }
+
+    /// Every piece on the board, from a8 to h1
+    pub fn pieces(&self) -> impl Iterator<Item = &Piece> {
+        self.board.iter().flatten()
+    }
+
+    /// Whether the side to move has a legal en passant capture. Positions
+    /// only differ by the en passant square when it does.
+    pub fn en_passant_possible(&self) -> bool {
+        let target = match self.en_passant {
+            Some(target) => target,
+            None => return false,
+        };
+        // Only pawns beside the one that just moved can take it
+        let pawns = self.bitboards.pieces[bitboard::side_index(&self.side)][PAWN_INDEX];
+        let capturers = bitboard::pawn_attacks(&self.side.opponent(), square_index(target)) & pawns;
+        bitboard::squares(capturers).any(|square| self.legal_targets(bitboard::square_position(square)) & bitboard::bit(target) != 0)
+    }
+
+    /// Whether the king of `side` is attacked
+    pub fn is_in_check(&self, side: &Side) -> bool {
+        king_in_check(&self.bitboards, side)
+    }
+
+    /// The destinations of valid_movements for the piece on `position` that
+    /// don't leave its own king in check. Empty if there is no piece there.
+    pub fn legal_movements(&self, position: Position) -> Vec<Position> {
+        bitboard::squares(self.legal_targets(position)).map(bitboard::square_position).collect()
+    }
+
+    // legal_movements as a bitboard
+    fn legal_targets(&self, position: Position) -> Bitboard {
+        self.legal_targets_within(position, !0)
+    }
+
+    // The legal targets that are also in `wanted`. Leaving the others out before
+    // checking for pins is what makes this cheaper than masking afterwards.
+    fn legal_targets_within(&self, position: Position, wanted: Bitboard) -> Bitboard {
+        let piece = match self.piece_at(position) {
+            Some(piece) => piece,
+            None => return 0,
+        };
+        let mut legal = 0;
+        let from = square_index(position);
+        // Kings are never captured, the game ends in checkmate before that
+        let mut candidates = piece.valid_targets(&self.bitboards) & !self.bitboards.pieces[bitboard::side_index(&piece.side.opponent())][KING_INDEX];
+        if let Some(target) = self.en_passant {
+            if piece.typ_index == PAWN_INDEX {
+                candidates |= bitboard::pawn_attacks(&piece.side, from) & bitboard::bit(target);
             }
-            return Some((piece.0 - amount, piece.1 + amount));
         }
-        fn move_diagonal_down_left(amount: usize, piece: (usize, usize)) -> Option<(usize, usize)> {
-            if amount + piece.0 >= 8 {
-                // Can't move down that much
-                return None;
+        candidates &= wanted;
+        // A piece that isn't on a line with its king can't be pinned, so unless the
+        // king is already in check (or en passant removes a second piece) all its
+        // moves are safe. Everything else is tried out.
+        let king = self.bitboards.king_square(&piece.side);
+        let may_expose_king = match king {
+            Some(king) => king == from || bitboard::queen_rays(king) & (1 << from) != 0 || king_in_check(&self.bitboards, &piece.side),
+            None => false,
+        };
+        let en_passant = match self.en_passant {
+            Some(target) if piece.typ_index == PAWN_INDEX => bitboard::bit(target),
+            _ => 0,
+        };
+        if !may_expose_king {
+            legal = candidates & !en_passant;
+            candidates &= en_passant;
+        }
+        for to in bitboard::squares(candidates) {
+            // Play the move on a copy of the bitboards and see if the king survives it
+            let mut bitboards = self.bitboards;
+            if let Some(captured) = self.piece_at(self.captured_position(piece, bitboard::square_position(to))) {
+                bitboards.remove(&captured.side, captured.typ_index, square_index(captured.position));
             }
-            if amount > piece.1 {
-                // Can't move left that much
-                return None;
+            bitboards.remove(&piece.side, piece.typ_index, from);
+            bitboards.put(&piece.side, piece.typ_index, to);
+            if !king_in_check(&bitboards, &piece.side) {
+                legal |= 1 << to;
             }
-            return Some((piece.0 + amount, piece.1 - amount));
         }
-        fn move_diagonal_down_right(amount: usize, piece: (usize, usize)) -> Option<(usize, usize)> {
-            if amount + piece.0 >= 8 {
-                // Can't move down that much
-                return None;
+        if piece.typ_index == KING_INDEX {
+            legal |= self.castling_targets(piece) & wanted;
+        }
+        legal
+    }
+
+    /// The square of the piece taken by moving `piece` to `destination`. That's
+    /// the destination itself, except for en passant where the pawn being
+    /// taken sits beside the capturing pawn.
+    pub fn captured_position(&self, piece: &Piece, destination: Position) -> Position {
+        if piece.typ_index == PAWN_INDEX && Some(destination) == self.en_passant {
+            (piece.position.0, destination.1)
+        } else {
+            destination
+        }
+    }
+
+    // Where the king can castle to. The king must not be in check, the squares
+    // between king and rook must be empty, and the king may not pass through
+    // or land on an attacked square.
+    fn castling_targets(&self, king: &Piece) -> Bitboard {
+        let row = if king.side == Side::White { 7 } else { 0 };
+        let (kingside, queenside) = match king.side {
+            Side::White => (self.castling.white_kingside, self.castling.white_queenside),
+            Side::Black => (self.castling.black_kingside, self.castling.black_queenside),
+        };
+        if king.position != (row, 4) || !(kingside || queenside) || self.is_in_check(&king.side) {
+            return 0;
+        }
+
+        let mut targets = 0;
+        let occupied = self.bitboards.occupied();
+        let rooks = self.bitboards.pieces[bitboard::side_index(&king.side)][ROOK_INDEX];
+        // (allowed, rook column, columns that must be empty, columns the king crosses)
+        for (allowed, rook_column, empty, crossed) in [
+            (kingside, 7, &[5, 6][..], [5, 6]),
+            (queenside, 0, &[1, 2, 3][..], [3, 2]),
+        ] {
+            if !allowed || rooks & bitboard::bit((row, rook_column)) == 0 {
+                continue;
             }
-            if amount + piece.1 >= 8 {
-                // Can't move right that much
-                return None;
+            if empty.iter().any(|column| occupied & bitboard::bit((row, *column)) != 0) {
+                continue;
             }
-            return Some((piece.0 + amount, piece.1 + amount));
-        }
-        fn find_all_movements(pieces: &Vec<Piece>, piece: &Piece, all_valid_movements: &mut Vec<(usize, usize)>, straight: bool, diagonal: bool, max_total_movement_amount: usize) {
-            // Populates all_valid_movements with the allowed movements of straight and/or diagonal without passing over pieces
-            // can't jump over another piece
-            // can capture piece
-
-            // Can only move up 7 (the entire board)
-            let mut done_up = !straight;
-            let mut done_down = !straight;
-            let mut done_left = !straight;
-            let mut done_right = !straight;
-            let mut done_up_left = !diagonal;
-            let mut done_down_right = !diagonal;
-            let mut done_up_right = !diagonal;
-            let mut done_down_left = !diagonal;
-            for amount in 1..(max_total_movement_amount + 1) {
-                let maybe_movement_up = move_up(amount, (piece.position.0, piece.position.1));
-                let maybe_movement_down = move_down(amount, (piece.position.0, piece.position.1));
-                let maybe_movement_left: Option<(usize, usize)> = move_left(amount, (piece.position.0, piece.position.1));
-                let maybe_movement_right: Option<(usize, usize)> = move_right(amount, (piece.position.0, piece.position.1));
-                let maybe_movement_up_left = move_diagonal_up_left(amount, (piece.position.0, piece.position.1));
-                let maybe_movement_down_right = move_diagonal_down_right(amount, (piece.position.0, piece.position.1));
-                let maybe_movement_down_left: Option<(usize, usize)> = move_diagonal_down_left(amount, (piece.position.0, piece.position.1));
-                let maybe_movement_up_right: Option<(usize, usize)> = move_diagonal_up_right(amount, (piece.position.0, piece.position.1));
-                for (maybe_movement, done) in [
-                    (maybe_movement_up, &mut done_up), 
-                    (maybe_movement_down, &mut done_down), 
-                    (maybe_movement_left, &mut done_left), 
-                    (maybe_movement_right, &mut done_right),
-                    (maybe_movement_up_left, &mut done_up_left), 
-                    (maybe_movement_down_right, &mut done_down_right), 
-                    (maybe_movement_down_left, &mut done_down_left), 
-                    (maybe_movement_up_right, &mut done_up_right)] {
-                    if *done == true {
-                        continue;
-                    }
-                    match maybe_movement {
-                        None => {
-                            // Not allowed, done.
-                            *done = true;
-                            continue;
-                        },
-                        Some((pos0, pos1)) => {
-                            // If another piece there that is ours, is done
-                            let maybe_other_piece = position_to_piece(&pieces, (pos0, pos1));
-                            match maybe_other_piece {
-                                Some(other_piece_index) => {
-                                    if pieces[other_piece_index].side == piece.side {
-                                        // Our piece. Not allowed, done.
-                                        *done = true;
-                                        continue;
-                                    }
-                                    // Not our piece, can take it over, then done.
-                                    all_valid_movements.push((pos0, pos1));
-                                    *done = true;
-                                    continue;
-                                },
-                                _ => {
-                                    // No piece there, can move!
-                                    all_valid_movements.push((pos0, pos1));
-                                }
-                            }
-                        }
-                    }
-                }
+            if crossed.iter().any(|column| is_attacked(&self.bitboards, (row, *column), &king.side.opponent())) {
+                continue;
             }
+            targets |= bitboard::bit((row, crossed[1]));
         }
+        targets
+    }
 
-        let mut all_valid_movements: Vec<(usize, usize)> = vec![];
-        match self.typ() {
-            PAWN => {
-                {
-                    // Can move up if white, down if black
-                    let one_move = if self.side == Side::Black { move_down(1, (self.position.0, self.position.1)) } else { move_up(1, (self.position.0, self.position.1)) };
-                    match one_move {
-                        Some((new_pos0, new_pos1)) => {
-                            // Check that there isn't a piece there
-                            if position_to_piece(&pieces, (new_pos0, new_pos1)).is_none() {
-                                // Can go there!
-                                all_valid_movements.push((new_pos0, new_pos1));
-                            }
-                        },
-                        _ => {}
-                    }
-                }
-                {
-                    // If at start position, can move two spots up/down
-                    if (self.side == Side::Black && self.position.0 == 1) || (self.side == Side::White && self.position.0 == 6) {
-                        // First turn!
-                        let two_move = if self.side == Side::Black { move_down(2, (self.position.0, self.position.1)) } else { move_up(2, (self.position.0, self.position.1)) };
-                        match two_move {
-                            Some((new_pos0, new_pos1)) => {
-                                // Check that there isn't a piece there
-                                if position_to_piece(&pieces, (new_pos0, new_pos1)).is_none() {
-                                    // Can go there!
-                                    all_valid_movements.push((new_pos0, new_pos1));
-                                }
-                            },
-                            _ => {}
-                        }
-                        }
-                }
-                {
-                    // Can only move diagonal by capturing
-                    let mut potential_diag_positions: Vec<Option<(usize, usize)>> = vec![];
-                    let diag_right = if self.side == Side::Black { move_diagonal_down_right(1, (self.position.0, self.position.1)) } else { move_diagonal_up_right(1, (self.position.0, self.position.1)) };
-                    let diag_left = if self.side == Side::Black { move_diagonal_down_left(1, (self.position.0, self.position.1)) } else { move_diagonal_up_left(1, (self.position.0, self.position.1)) };
-                    potential_diag_positions.push(diag_right);
-                    potential_diag_positions.push(diag_left);
-
-                    for diag in potential_diag_positions {
-                        match diag {
-                            Some((pos_0, pos_1)) => {
-                                let maybe_piece_index_approaching = position_to_piece(&pieces, (pos_0, pos_1));
-                                match maybe_piece_index_approaching {
-                                    Some(piece_index_approaching) => {
-                                        if pieces[piece_index_approaching].side != self.side {
-                                            // Enemy!
-                                            all_valid_movements.push((pos_0, pos_1));
-                                        }
-                                    },
-                                    None => {
-                                        // Can't move there if no enemy piece there
-                                    }
-                                }
-                            },
-                            _ => {}
-                        }
+    /// Every legal move for the side to move. A pawn reaching the last rank
+    /// gets one move per promotion piece.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        self.generate_moves(false)
+    }
+
+    /// The legal captures and promotions for the side to move, the moves
+    /// that change the material. Quicker than filtering legal_moves.
+    pub fn legal_captures(&self) -> Vec<Move> {
+        self.generate_moves(true)
+    }
+
+    fn generate_moves(&self, captures_only: bool) -> Vec<Move> {
+        let mut moves = vec![];
+        let enemies = self.bitboards.side(&self.side.opponent());
+        for from in bitboard::squares(self.bitboards.side(&self.side)) {
+            let from = bitboard::square_position(from);
+            let piece = match self.piece_at(from) {
+                Some(piece) => piece,
+                None => continue,
+            };
+            let promoting = piece.typ_index == PAWN_INDEX;
+            let wanted = if !captures_only {
+                !0
+            } else if promoting {
+                // A pawn reaching the first or last rank promotes, capture or not
+                enemies | 0xff | 0xff << 56 | self.en_passant.map_or(0, bitboard::bit)
+            } else {
+                enemies
+            };
+            for to in bitboard::squares(self.legal_targets_within(from, wanted)).map(bitboard::square_position) {
+                let flags = self.move_flags(piece, to);
+                if promoting && (to.0 == 0 || to.0 == 7) {
+                    for promotion in [QUEEN_INDEX, ROOK_INDEX, BISHOP_INDEX, KNIGHT_INDEX] {
+                        moves.push(Move { from, to, promotion: Some(promotion), flags });
                     }
+                } else {
+                    moves.push(Move { from, to, promotion: None, flags });
                 }
-            },
-            ROOK => {
-                find_all_movements(&pieces, &self, &mut all_valid_movements, true, false, 7);
             }
-            KNIGHT => {
-                // up,down,left,right by 2 then right/left by 1
-                // Curry all the move helpers to make the main helper cleaner
-                let move_right = |amount| move |piece| move_right(amount, piece);
-                let move_left = |amount| move |piece| move_left(amount, piece);
-                let move_up = |amount| move |piece| move_up(amount, piece);
-                let move_down = |amount| move |piece| move_down(amount, piece);
-                // Helper which tries to move the knight by two moves. If the move is valid, adds to all_valid_movements
-                fn move_fn(
-                    first_move: impl Fn((usize, usize)) -> Option<(usize, usize)>, 
-                    second_move: impl Fn((usize, usize)) -> Option<(usize, usize)>, 
-                    start_pos: (usize, usize),
-                    pieces: &Vec<Piece>,
-                    piece: &Piece,
-                    all_valid_movements: &mut Vec<(usize, usize)>)  {
-                    // first_move and second_move are curried with the amount they need to move by (2 or 1 each)
-                    match first_move(start_pos) {
-                        Some((pov0, pov1)) =>  {
-                            // Where second move should start from
-                            match second_move((pov0, pov1)) {
-                                Some((final_pov0, final_pov1))  => {
-                                    // Can only move here if enemy piece or no piece
-                                    match position_to_piece(&pieces, (final_pov0, final_pov1)) {
-                                        None => {
-                                            // Can go there
-                                            all_valid_movements.push((final_pov0, final_pov1));
-                                        },
-                                        Some(other_index) => {
-                                            if pieces[other_index].side != piece.side {
-                                                // Enemy, so we can move there
-                                                all_valid_movements.push((final_pov0, final_pov1));
-                                            }
-                                        }
-                                    }
-                                },
-                                None => {
-                                    // Can't move there
-                                }
-                            }
-                        },
-                        None => {
-                            // Can't move there
-                        }
-                    }
-                }
-                // up 2, left 1
-                move_fn(
-                    move_up(2), 
-                    move_left(1), 
-                    (self.position.0, self.position.1),
-                    &pieces,
-                    &self,
-                    &mut all_valid_movements);
-                // up 2, right 1
-                move_fn(
-                    move_up(2), 
-                    move_right(1), 
-                    (self.position.0, self.position.1),
-                    &pieces,
-                    &self,
-                    &mut all_valid_movements);
-                // down 2, left 1
-                move_fn(
-                    move_down(2), 
-                    move_left(1), 
-                    (self.position.0, self.position.1),
-                    &pieces,
-                    &self,
-                    &mut all_valid_movements);
-                // down 2, right 1
-                move_fn(
-                    move_down(2), 
-                    move_right(1), 
-                    (self.position.0, self.position.1),
-                    &pieces,
-                    &self,
-                    &mut all_valid_movements);
-                // right 2, up 1
-                move_fn(
-                    move_right(2), 
-                    move_up(1), 
-                    (self.position.0, self.position.1),
-                    &pieces,
-                    &self,
-                    &mut all_valid_movements);
-                // right 2, down 1
-                move_fn(
-                    move_right(2), 
-                    move_down(1), 
-                    (self.position.0, self.position.1),
-                    &pieces,
-                    &self,
-                    &mut all_valid_movements);
-                // left 2, up 1
-                move_fn(
-                    move_left(2), 
-                    move_up(1), 
-                    (self.position.0, self.position.1),
-                    &pieces,
-                    &self,
-                    &mut all_valid_movements);
-                // left 2, down 1
-                move_fn(
-                    move_left(2), 
-                    move_down(1), 
-                    (self.position.0, self.position.1),
-                    &pieces,
-                    &self,
-                    &mut all_valid_movements);
-            },
-            BISHOP => {
-                // can move max 7 diagonal all directions, not over any pieces
-                // very similar to rooks except diagonal instead
-                find_all_movements(&pieces, &self, &mut all_valid_movements, false, true, 7);
-            },
-            QUEEN => {
-                find_all_movements(&pieces, &self, &mut all_valid_movements, true, true, 7);
-            },
-            KING => {
-                // Any direction, just by one
-                find_all_movements(&pieces, &self, &mut all_valid_movements, true, true, 1);
-            },
+        }
+        moves
+    }
+
+    /// Whether any piece of `side` has a legal move
+    pub fn has_legal_moves(&self, side: &Side) -> bool {
+        bitboard::squares(self.bitboards.side(side)).any(|square| self.legal_targets(bitboard::square_position(square)) != 0)
+    }
+
+    /// Checkmate and the draws that end the game without anyone claiming them
+    pub fn status(&self) -> GameStatus {
+        if !self.has_legal_moves(&self.side) {
+            if self.is_in_check(&self.side) {
+                return GameStatus::Checkmate { winner: self.side.opponent() };
+            }
+            return GameStatus::Draw { reason: DrawReason::Stalemate };
+        }
+        if self.insufficient_material() {
+            GameStatus::Draw { reason: DrawReason::InsufficientMaterial }
+        } else if self.halfmove_clock >= 150 {
+            GameStatus::Draw { reason: DrawReason::SeventyFiveMoveRule }
+        } else if self.repetitions() >= 5 {
+            GameStatus::Draw { reason: DrawReason::FivefoldRepetition }
+        } else {
+            GameStatus::Ongoing
+        }
+    }
+
+    /// A draw the side to move may claim, but doesn't have to
+    pub fn claimable_draw(&self) -> Option<DrawReason> {
+        if self.halfmove_clock >= 100 {
+            Some(DrawReason::FiftyMoveRule)
+        } else if self.repetitions() >= 3 {
+            Some(DrawReason::ThreefoldRepetition)
+        } else {
+            None
+        }
+    }
+
+    /// How many times the current position has occurred
+    pub fn repetitions(&self) -> usize {
+        match self.position_history.last() {
+            Some(current) => self.position_history.iter().filter(|key| *key == current).count(),
+            None => 0,
+        }
+    }
+
+    /// King against king, king and a minor piece against king, or only
+    /// bishops left that all stand on the same colour
+    pub fn insufficient_material(&self) -> bool {
+        let others: Vec<&Piece> = self.pieces().filter(|piece| piece.typ() != KING).collect();
+        match others[..] {
+            [] => true,
+            [piece] => piece.typ() == KNIGHT || piece.typ() == BISHOP,
             _ => {
+                let square_colour = |piece: &Piece| (piece.position.0 + piece.position.1) % 2;
+                others.iter().all(|piece| piece.typ() == BISHOP)
+                    && others.iter().all(|piece| square_colour(piece) == square_colour(others[0]))
             },
         }
-        return all_valid_movements;
+    }
+}
+
+// The (from, to) columns of the rook when the king castles to `king_to`
+fn castling_rook_columns(king_to: Position) -> (usize, usize) {
+    if king_to.1 == 6 { (7, 5) } else { (0, 3) }
+}
+
+impl Piece {
+    /// The name of this piece's type, one of PIECE_NAMES
+    pub fn typ(&self) -> &str {
+        PIECE_NAMES[self.typ_index]
+    }
+
+    pub fn transform_typ(&mut self, new_typ: &str) {
+        for (i, name) in PIECE_NAMES.iter().enumerate() {
+            if *name == new_typ {
+                self.typ_index = i;
+                return;
+            }
+        }
+        panic!("Invalid piece type change: {}", new_typ);
+    }
+
+    /// Where this piece can move by its own movement rules, ignoring checks,
+    /// castling and en passant. See Game::legal_movements for those.
+    pub fn valid_movements(&self, bitboards: &Bitboards) -> Vec<(usize, usize)> {
+        bitboard::squares(self.valid_targets(bitboards)).map(bitboard::square_position).collect()
+    }
+
+    // valid_movements as a bitboard
+    fn valid_targets(&self, bitboards: &Bitboards) -> Bitboard {
+        let square = square_index(self.position);
+        let own = bitboards.side(&self.side);
+        let occupied = bitboards.occupied();
+        if self.typ_index != PAWN_INDEX {
+            return bitboard::attacks(self.typ_index, square, occupied) & !own;
+        }
+
+        // Can only move diagonal by capturing
+        let mut targets = bitboard::pawn_attacks(&self.side, square) & bitboards.side(&self.side.opponent());
+        // Can move up if white, down if black, onto an empty square
+        let (forward, start_row) = if self.side == Side::White { (-8, 6) } else { (8, 1) };
+        if let Some(one_move) = square.checked_add_signed(forward).filter(|one_move| *one_move < 64) {
+            if occupied & (1 << one_move) == 0 {
+                targets |= 1 << one_move;
+                // If at start position, can move two spots up/down
+                let two_move = one_move.wrapping_add_signed(forward);
+                if self.position.0 == start_row && occupied & (1 << two_move) == 0 {
+                    targets |= 1 << two_move;
+                }
+            }
+        }
+        targets
     }
 }
@@ -0,0 +1,189 @@
+//! Standard Algebraic Notation, the way moves are written in books and PGN:
+//! `Nf3`, `exd5`, `O-O`, `e8=Q+`, `Raxd1#`.
+
+use std::fmt;
+
+use crate::fen;
+use crate::piece::{self, Game, Move, Position};
+
+// The SAN letter of each piece type, indexed by typ_index. Pawns don't get one.
+const PIECE_LETTERS: [char; 6] = ['P', 'R', 'N', 'B', 'Q', 'K'];
+
+/// Why a SAN move couldn't be turned into a Move
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum SanError {
+    /// Nothing was given
+    Empty,
+    /// The text isn't shaped like a SAN move
+    Invalid,
+    /// No legal move matches
+    NoSuchMove,
+    /// More than one legal move matches, it needs a file or rank to tell them apart
+    Ambiguous,
+}
+
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SanError::Empty => write!(f, "no move given"),
+            SanError::Invalid => write!(f, "not a move in algebraic notation"),
+            SanError::NoSuchMove => write!(f, "no legal move matches"),
+            SanError::Ambiguous => write!(f, "more than one piece can make that move"),
+        }
+    }
+}
+
+impl std::error::Error for SanError {}
+
+/// Writes `movement`, which must be legal in `game`, in SAN. The check or
+/// mate suffix comes from playing the move on a copy of the game.
+pub fn move_to_san(game: &Game, movement: &Move) -> String {
+    let mut san = String::new();
+    let piece = match game.piece_at(movement.from) {
+        Some(piece) => *piece,
+        None => return movement.to_string(),
+    };
+
+    if movement.flags.castle {
+        san.push_str(if movement.to.1 == 6 { "O-O" } else { "O-O-O" });
+    } else {
+        if piece.typ_index == piece::PAWN_INDEX {
+            // Pawn captures always say which file the pawn came from
+            if movement.flags.capture {
+                san.push(file_letter(movement.from));
+            }
+        } else {
+            san.push(PIECE_LETTERS[piece.typ_index]);
+            san.push_str(&disambiguation(game, &piece, movement));
+        }
+        if movement.flags.capture {
+            san.push('x');
+        }
+        san.push_str(&fen::position_to_square(movement.to));
+        if let Some(promotion) = movement.promotion {
+            san.push('=');
+            san.push(PIECE_LETTERS[promotion]);
+        }
+    }
+
+    let mut after = game.clone();
+    after.make_move(*movement);
+    if after.is_in_check(&after.side) {
+        san.push(if after.has_legal_moves(&after.side) { '+' } else { '#' });
+    }
+    san
+}
+
+// The file, rank or whole square needed to tell `movement` apart from the
+// same kind of piece going to the same square
+fn disambiguation(game: &Game, piece: &piece::Piece, movement: &Move) -> String {
+    let others: Vec<Position> = game.legal_moves()
+        .iter()
+        .filter(|other| other.to == movement.to && other.from != movement.from)
+        .filter(|other| game.piece_at(other.from).is_some_and(|other| other.typ_index == piece.typ_index))
+        .map(|other| other.from)
+        .collect();
+    if others.is_empty() {
+        String::new()
+    } else if others.iter().all(|other| other.1 != movement.from.1) {
+        file_letter(movement.from).to_string()
+    } else if others.iter().all(|other| other.0 != movement.from.0) {
+        rank_digit(movement.from).to_string()
+    } else {
+        fen::position_to_square(movement.from)
+    }
+}
+
+/// Finds the legal move in `game` written as `san`. Check, mate and
+/// annotation suffixes (`+`, `#`, `!`, `?`) are ignored, as is a missing
+/// capture `x`. Castling may be written with zeros, and a promotion without
+/// the `=`; leaving the promotion out promotes to a queen.
+pub fn san_to_move(game: &Game, san: &str) -> Result<Move, SanError> {
+    let san = san.trim().trim_end_matches(['+', '#', '!', '?']);
+    if san.is_empty() {
+        return Err(SanError::Empty);
+    }
+
+    if san == "O-O" || san == "0-0" || san == "O-O-O" || san == "0-0-0" {
+        let column = if san.len() == 3 { 6 } else { 2 };
+        let matching: Vec<Move> = game.legal_moves()
+            .into_iter()
+            .filter(|movement| movement.flags.castle && movement.to.1 == column)
+            .collect();
+        return matching.first().copied().ok_or(SanError::NoSuchMove);
+    }
+
+    let mut chars: Vec<char> = san.chars().collect();
+    let typ_index = match PIECE_LETTERS.iter().position(|letter| *letter == chars[0]) {
+        Some(typ_index) => {
+            chars.remove(0);
+            typ_index
+        },
+        None => piece::PAWN_INDEX,
+    };
+
+    // "e8=Q" or "e8Q"
+    let mut promotion = None;
+    if let Some(last) = chars.last() {
+        if let Some(typ_index) = PIECE_LETTERS.iter().position(|letter| letter == last) {
+            promotion = Some(typ_index);
+            chars.pop();
+            if chars.last() == Some(&'=') {
+                chars.pop();
+            }
+        }
+    }
+
+    // The destination square is at the end, anything before it is the capture
+    // marker and the file and/or rank of the piece moving
+    if chars.len() < 2 {
+        return Err(SanError::Invalid);
+    }
+    let to = square(chars[chars.len() - 2], chars[chars.len() - 1]).ok_or(SanError::Invalid)?;
+    chars.truncate(chars.len() - 2);
+    if chars.last() == Some(&'x') {
+        chars.pop();
+    }
+    let mut from_file = None;
+    let mut from_rank = None;
+    for hint in chars {
+        match hint {
+            'a'..='h' if from_file.is_none() && from_rank.is_none() => from_file = Some(hint as usize - 'a' as usize),
+            '1'..='8' if from_rank.is_none() => from_rank = Some(8 - hint.to_digit(10).unwrap() as usize),
+            _ => return Err(SanError::Invalid),
+        }
+    }
+
+    let matching: Vec<Move> = game.legal_moves()
+        .into_iter()
+        .filter(|movement| movement.to == to && !movement.flags.castle)
+        .filter(|movement| game.piece_at(movement.from).is_some_and(|piece| piece.typ_index == typ_index))
+        .filter(|movement| from_file.is_none_or(|file| movement.from.1 == file))
+        .filter(|movement| from_rank.is_none_or(|rank| movement.from.0 == rank))
+        .filter(|movement| match (promotion, movement.promotion) {
+            (None, None) => true,
+            (None, Some(promoted)) => promoted == piece::QUEEN_INDEX,
+            (Some(wanted), promoted) => promoted == Some(wanted),
+        })
+        .collect();
+    match matching[..] {
+        [movement] => Ok(movement),
+        [] => Err(SanError::NoSuchMove),
+        _ => Err(SanError::Ambiguous),
+    }
+}
+
+fn file_letter(position: Position) -> char {
+    (b'a' + position.1 as u8) as char
+}
+
+fn rank_digit(position: Position) -> char {
+    (b'8' - position.0 as u8) as char
+}
+
+fn square(file: char, rank: char) -> Option<Position> {
+    match (file, rank) {
+        ('a'..='h', '1'..='8') => Some((8 - rank.to_digit(10)? as usize, file as usize - 'a' as usize)),
+        _ => None,
+    }
+}
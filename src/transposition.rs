@@ -0,0 +1,173 @@
+//! The transposition table: a fixed-size hash table of search results keyed
+//! by Zobrist hash, so a position reached again, by another move order or in
+//! the next iteration, doesn't have to be searched all over again.
+//!
+//! Each slot is two atomics, the data and the key xored with the data, so
+//! threads can share a table without locks. A slot torn by two threads
+//! writing at once no longer matches its key and reads as empty.
+
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+use crate::bitboard;
+use crate::piece::{self, Move, MoveFlags};
+
+/// Size of the table when nothing else is asked for, in megabytes
+pub const DEFAULT_MEGABYTES: usize = 16;
+
+/// Biggest table allowed, in megabytes
+pub const MAX_MEGABYTES: usize = 4096;
+
+/// What a stored score says about the real score of the position
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Bound {
+    /// The real score
+    Exact,
+    /// The real score is at least this, the search stopped at a beta cutoff
+    Lower,
+    /// The real score is at most this, no move got above alpha
+    Upper,
+}
+
+/// One search result
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Entry {
+    /// How many plies deep the position was searched
+    pub depth: usize,
+    /// Mate scores count plies from this position, not from the root
+    pub score: i32,
+    pub bound: Bound,
+    /// The best move found, if any move was searched all the way
+    pub best_move: Option<Move>,
+}
+
+#[derive(Default)]
+struct Slot {
+    // The hash xored with data
+    check: AtomicU64,
+    data: AtomicU64,
+}
+
+/// A fixed-size table of Entry, shared between searches
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+    // Goes up with every search, so entries from old searches can be told apart and replaced
+    generation: AtomicU8,
+}
+
+impl TranspositionTable {
+    /// An empty table taking about `megabytes` of memory, at least one slot
+    pub fn new(megabytes: usize) -> TranspositionTable {
+        let count = (megabytes.min(MAX_MEGABYTES) * 1024 * 1024 / std::mem::size_of::<Slot>()).max(1);
+        TranspositionTable {
+            slots: (0..count).map(|_| Slot::default()).collect(),
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    /// How many entries fit
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Forgets everything, for a new game
+    pub fn clear(&self) {
+        for slot in &self.slots {
+            slot.check.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+        self.generation.store(0, Ordering::Relaxed);
+    }
+
+    /// Called at the start of each search, entries stored before are then
+    /// the first to be replaced
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The entry stored for the position with `hash`, if there is one
+    pub fn probe(&self, hash: u64) -> Option<Entry> {
+        let slot = self.slot(hash);
+        let data = slot.data.load(Ordering::Relaxed);
+        if data == 0 || slot.check.load(Ordering::Relaxed) ^ data != hash {
+            return None;
+        }
+        Some(unpack(data).0)
+    }
+
+    /// Stores `entry` for the position with `hash`. Whatever else is in its
+    /// slot is kept instead if it's from this search and was searched deeper.
+    pub fn store(&self, hash: u64, entry: Entry) {
+        let slot = self.slot(hash);
+        let generation = self.generation.load(Ordering::Relaxed);
+        let old = slot.data.load(Ordering::Relaxed);
+        if old != 0 {
+            let same_position = slot.check.load(Ordering::Relaxed) ^ old == hash;
+            let (old_entry, old_generation) = unpack(old);
+            if !same_position && old_generation == generation && old_entry.depth > entry.depth {
+                return;
+            }
+            // A result without a move shouldn't lose the move we already know for this position
+            if same_position && entry.best_move.is_none() && old_entry.depth > entry.depth {
+                return;
+            }
+        }
+        let data = pack(&entry, generation);
+        slot.data.store(data, Ordering::Relaxed);
+        slot.check.store(hash ^ data, Ordering::Relaxed);
+    }
+
+    fn slot(&self, hash: u64) -> &Slot {
+        &self.slots[(hash % self.slots.len() as u64) as usize]
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> TranspositionTable {
+        TranspositionTable::new(DEFAULT_MEGABYTES)
+    }
+}
+
+// An entry in 64 bits, lowest first: 24 bits of score, 8 of depth, 2 of
+// bound, 8 of generation, 1 for whether there is a move, then the move: 6
+// bits for each square, 3 for the promotion and 4 for the flags. Bit 63 is
+// always set so a stored entry is never 0, which is an empty slot.
+fn pack(entry: &Entry, generation: u8) -> u64 {
+    let mut data = (entry.score as u64 & 0xff_ffff)
+        | (entry.depth.min(255) as u64) << 24
+        | (entry.bound as u64) << 32
+        | (generation as u64) << 34
+        | 1 << 63;
+    if let Some(movement) = entry.best_move {
+        let flags = [movement.flags.capture, movement.flags.castle, movement.flags.en_passant, movement.flags.double_push]
+            .iter()
+            .enumerate()
+            .fold(0, |flags, (index, flag)| flags | (*flag as u64) << index);
+        data |= 1 << 42
+            | (piece::square_index(movement.from) as u64) << 43
+            | (piece::square_index(movement.to) as u64) << 49
+            | (movement.promotion.unwrap_or(0) as u64) << 55
+            | flags << 58;
+    }
+    data
+}
+
+fn unpack(data: u64) -> (Entry, u8) {
+    let bits = |shift: u32, count: u32| (data >> shift) & ((1 << count) - 1);
+    // Sign-extend the 24 bit score
+    let score = ((bits(0, 24) as i32) << 8) >> 8;
+    let bound = match bits(32, 2) {
+        0 => Bound::Exact,
+        1 => Bound::Lower,
+        _ => Bound::Upper,
+    };
+    let best_move = (bits(42, 1) == 1).then(|| {
+        let flags = bits(58, 4);
+        Move {
+            from: bitboard::square_position(bits(43, 6) as usize),
+            to: bitboard::square_position(bits(49, 6) as usize),
+            promotion: Some(bits(55, 3) as usize).filter(|promotion| *promotion != 0),
+            flags: MoveFlags { capture: flags & 1 != 0, castle: flags & 2 != 0, en_passant: flags & 4 != 0, double_push: flags & 8 != 0 },
+        }
+    });
+    (Entry { depth: bits(24, 8) as usize, score, bound, best_move }, bits(34, 8) as u8)
+}
@@ -0,0 +1,245 @@
+//! The Universal Chess Interface, the text protocol chess GUIs use to talk
+//! to engines. Commands come in one per line and the answers go out the
+//! same way. Searches run on their own thread so `stop` can interrupt them.
+
+use std::fs;
+use std::io::{BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::engine::{self, SearchInfo, SearchLimits};
+use crate::fen;
+use crate::piece::{self, Game, Side};
+use crate::polyglot::{Book, Selection};
+use crate::transposition::{self, TranspositionTable};
+
+// Time kept back when working out how long to think, for talking to the GUI
+const MOVE_OVERHEAD: Duration = Duration::from_millis(50);
+
+/// Speaks UCI on `input` and `output` until `quit` or the end of the input
+pub fn run<R: BufRead, W: Write + Send + 'static>(input: R, output: W) {
+    let mut uci = Uci {
+        game: fen::fen_to_board(fen::START_FEN).expect("start position is valid FEN"),
+        output: Arc::new(Mutex::new(output)),
+        table: Arc::new(TranspositionTable::default()),
+        book: None,
+        stop: Arc::new(AtomicBool::new(false)),
+        searching: None,
+    };
+    for line in input.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        if !uci.command(&line) {
+            break;
+        }
+    }
+    uci.stop_search();
+}
+
+struct Uci<W> {
+    // The position set by the last `position` command
+    game: Game,
+    output: Arc<Mutex<W>>,
+    // Kept from one search to the next, until "ucinewgame"
+    table: Arc<TranspositionTable>,
+    // Set with the Book option, moves in it are played without searching
+    book: Option<Book>,
+    // Tells the running search to wrap up
+    stop: Arc<AtomicBool>,
+    searching: Option<JoinHandle<()>>,
+}
+
+impl<W: Write + Send + 'static> Uci<W> {
+    // Handles one line. Returns false on `quit`.
+    fn command(&mut self, line: &str) -> bool {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.first() {
+            Some(&"uci") => {
+                self.send(&format!("id name {} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")));
+                // Cargo separates the authors with colons
+                self.send(&format!("id author {}", env!("CARGO_PKG_AUTHORS").replace(':', ", ")));
+                self.send(&format!("option name Hash type spin default {} min 1 max {}", transposition::DEFAULT_MEGABYTES, transposition::MAX_MEGABYTES));
+                self.send("option name Book type string default <empty>");
+                self.send("uciok");
+            },
+            Some(&"isready") => self.send("readyok"),
+            Some(&"ucinewgame") => {
+                self.stop_search();
+                self.game = fen::fen_to_board(fen::START_FEN).expect("start position is valid FEN");
+                self.table.clear();
+            },
+            Some(&"setoption") => {
+                self.stop_search();
+                if let Err(error) = self.set_option(&words[1..]) {
+                    self.send(&format!("info string {}", error));
+                }
+            },
+            Some(&"position") => {
+                self.stop_search();
+                if let Err(error) = self.position(&words[1..]) {
+                    self.send(&format!("info string {}", error));
+                }
+            },
+            Some(&"go") => {
+                self.stop_search();
+                self.go(&words[1..]);
+            },
+            Some(&"stop") => self.stop_search(),
+            Some(&"quit") => return false,
+            // Unknown commands are ignored, as the protocol asks
+            _ => {},
+        }
+        true
+    }
+
+    fn send(&self, line: &str) {
+        send(&self.output, line);
+    }
+
+    // "startpos" or "fen <fen>", optionally followed by "moves" and the moves
+    // played since. On error the position is left as it was.
+    fn position(&mut self, words: &[&str]) -> Result<(), String> {
+        let (setup, moves) = match words.iter().position(|word| *word == "moves") {
+            Some(index) => (&words[..index], &words[index + 1..]),
+            None => (words, &[][..]),
+        };
+        let mut game = match setup {
+            ["startpos"] => fen::fen_to_board(fen::START_FEN).expect("start position is valid FEN"),
+            ["fen", fields @ ..] => fen::fen_to_board(&fields.join(" ")).map_err(|error| format!("invalid FEN: {}", error))?,
+            _ => return Err("expected \"position startpos\" or \"position fen <fen>\"".to_string()),
+        };
+        for text in moves {
+            let movement = piece::parse_movement(text).map_err(|error| format!("invalid move {}: {}", text, error))?;
+            let movement = game.legal_move(&movement).map_err(|error| format!("can't play {}: {}", text, error))?;
+            game.make_move(movement);
+        }
+        self.game = game;
+        Ok(())
+    }
+
+    // "name <name> value <value>", for Hash or Book
+    fn set_option(&mut self, words: &[&str]) -> Result<(), String> {
+        let value_index = words.iter().position(|word| *word == "value").unwrap_or(words.len());
+        let name = match words.first() {
+            Some(&"name") => words[1..value_index].join(" "),
+            _ => return Err("expected \"setoption name <name> value <value>\"".to_string()),
+        };
+        let value = words.get(value_index + 1..).unwrap_or_default().join(" ");
+        // Option names aren't case sensitive
+        if name.eq_ignore_ascii_case("hash") {
+            let megabytes = value.parse::<usize>().map_err(|_| format!("invalid Hash size {}", value))?;
+            self.table = Arc::new(TranspositionTable::new(megabytes.clamp(1, transposition::MAX_MEGABYTES)));
+            Ok(())
+        } else if name.eq_ignore_ascii_case("book") {
+            // GUIs send "<empty>" for an empty string
+            if value.is_empty() || value == "<empty>" {
+                self.book = None;
+                return Ok(());
+            }
+            let bytes = fs::read(&value).map_err(|error| format!("can't read {}: {}", value, error))?;
+            self.book = Some(Book::from_bytes(&bytes).map_err(|error| format!("{} isn't a Polyglot book: {}", value, error))?);
+            Ok(())
+        } else {
+            Err(format!("no such option {}", name))
+        }
+    }
+
+    // Starts searching the current position on another thread, which sends
+    // "info" lines as it goes and "bestmove" at the end
+    fn go(&mut self, words: &[&str]) {
+        let limits = search_limits(words, &self.game.side);
+        let infinite = words.contains(&"infinite");
+        // A book move needs no thinking, unless the GUI wants to analyse
+        if !infinite {
+            if let Some(movement) = self.book.as_ref().and_then(|book| book.choose(&self.game, Selection::Weighted)) {
+                self.send(&format!("bestmove {}", movement));
+                return;
+            }
+        }
+        let game = self.game.clone();
+        let output = Arc::clone(&self.output);
+        let table = Arc::clone(&self.table);
+        let stop = Arc::clone(&self.stop);
+        self.stop.store(false, Ordering::Relaxed);
+        self.searching = Some(thread::spawn(move || {
+            let best = engine::search(&game, &limits, &table, &stop, |info| send(&output, &info_line(info)));
+            // An infinite search may not answer before it's told to stop, even if
+            // it has nothing left to search
+            while infinite && !stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(5));
+            }
+            match best {
+                Some(movement) => send(&output, &format!("bestmove {}", movement)),
+                // No legal moves, UCI has a null move for that
+                None => send(&output, "bestmove 0000"),
+            }
+        }));
+    }
+
+    // Stops the running search, if any, and waits for its bestmove
+    fn stop_search(&mut self) {
+        if let Some(searching) = self.searching.take() {
+            self.stop.store(true, Ordering::Relaxed);
+            searching.join().ok();
+        }
+    }
+}
+
+fn send<W: Write>(output: &Mutex<W>, line: &str) {
+    let mut output = output.lock().expect("nothing panics while holding the output");
+    writeln!(output, "{}", line).ok();
+    output.flush().ok();
+}
+
+// The arguments of "go". A fixed movetime is used as is; otherwise a share of
+// the clock of the side to move, plus most of its increment, is given to this
+// move. "infinite" or no limits at all searches until "stop".
+fn search_limits(words: &[&str], side: &Side) -> SearchLimits {
+    let mut limits = SearchLimits::default();
+    let mut clock = None;
+    let mut increment = Duration::ZERO;
+    let mut moves_to_go = 30;
+    let mut infinite = false;
+    let number = |index: usize| words.get(index + 1).and_then(|value| value.parse::<u64>().ok());
+    for (index, word) in words.iter().enumerate() {
+        match (*word, side) {
+            ("depth", _) => limits.depth = number(index).map(|depth| depth as usize),
+            ("movetime", _) => limits.time = number(index).map(Duration::from_millis),
+            ("wtime", Side::White) | ("btime", Side::Black) => clock = number(index).map(Duration::from_millis),
+            ("winc", Side::White) | ("binc", Side::Black) | ("inc", _) => increment = number(index).map_or(increment, Duration::from_millis),
+            ("movestogo", _) => moves_to_go = number(index).unwrap_or(moves_to_go).max(1),
+            ("infinite", _) => infinite = true,
+            _ => {},
+        }
+    }
+    if infinite {
+        return SearchLimits::default();
+    }
+    if limits.time.is_none() {
+        if let Some(clock) = clock {
+            let share = clock / moves_to_go as u32 + increment * 3 / 4;
+            limits.time = Some(share.min(clock.saturating_sub(MOVE_OVERHEAD)));
+        }
+    }
+    limits
+}
+
+// An "info" line for one finished depth of the search
+fn info_line(info: &SearchInfo) -> String {
+    let score = match info.mate_in() {
+        Some(moves) => format!("mate {}", moves),
+        None => format!("cp {}", info.score),
+    };
+    let milliseconds = info.time.as_millis();
+    let nps = info.nodes as u128 * 1000 / milliseconds.max(1);
+    let mut line = format!("info depth {} score {} nodes {} nps {} time {}", info.depth, score, info.nodes, nps, milliseconds);
+    if !info.pv.is_empty() {
+        let pv: Vec<String> = info.pv.iter().map(|movement| movement.to_string()).collect();
+        line.push_str(&format!(" pv {}", pv.join(" ")));
+    }
+    line
+}
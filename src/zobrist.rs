@@ -0,0 +1,94 @@
+//! Zobrist hashing: a 64-bit key for a position made by xoring together a
+//! random number for every piece on its square, one for Black to move, one
+//! per castling right and one for the en passant file. A move only changes a
+//! handful of those, so Game keeps its key up to date as moves are made
+//! instead of hashing the whole board every time.
+
+use crate::bitboard;
+use crate::piece::{self, Game, Side};
+
+struct Keys {
+    // Indexed by side_index, typ_index and square_index
+    pieces: [[[u64; 64]; 6]; 2],
+    black_to_move: u64,
+    // White kingside, white queenside, black kingside, black queenside
+    castling: [u64; 4],
+    // Indexed by column
+    en_passant: [u64; 8],
+}
+
+// Generated at compile time from a fixed seed, so keys are the same every run
+const KEYS: Keys = generate_keys();
+
+// SplitMix64, small and good enough for hashing
+const fn next_random(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+const fn generate_keys() -> Keys {
+    let mut state = 0x0c4e_55ed_2024_0001;
+    let mut keys = Keys { pieces: [[[0; 64]; 6]; 2], black_to_move: 0, castling: [0; 4], en_passant: [0; 8] };
+    // No for loops in const fns
+    let mut side = 0;
+    while side < 2 {
+        let mut typ_index = 0;
+        while typ_index < 6 {
+            let mut square = 0;
+            while square < 64 {
+                keys.pieces[side][typ_index][square] = next_random(&mut state);
+                square += 1;
+            }
+            typ_index += 1;
+        }
+        side += 1;
+    }
+    keys.black_to_move = next_random(&mut state);
+    let mut index = 0;
+    while index < 4 {
+        keys.castling[index] = next_random(&mut state);
+        index += 1;
+    }
+    let mut column = 0;
+    while column < 8 {
+        keys.en_passant[column] = next_random(&mut state);
+        column += 1;
+    }
+    keys
+}
+
+/// The key for a piece of `side` and `typ_index` standing on `square`
+pub fn piece_key(side: &Side, typ_index: usize, square: usize) -> u64 {
+    KEYS.pieces[bitboard::side_index(side)][typ_index][square]
+}
+
+/// The part of the key that isn't piece placement: side to move, castling
+/// rights, and the en passant file when an en passant capture is possible
+pub fn state_key(game: &Game) -> u64 {
+    let mut key = 0;
+    if game.side == Side::Black {
+        key ^= KEYS.black_to_move;
+    }
+    let rights = [game.castling.white_kingside, game.castling.white_queenside, game.castling.black_kingside, game.castling.black_queenside];
+    for (right, castling_key) in rights.iter().zip(KEYS.castling) {
+        if *right {
+            key ^= castling_key;
+        }
+    }
+    // A double push that can't be answered en passant leaves the same
+    // position as any other move, so it mustn't change the key
+    if let Some(target) = game.en_passant.filter(|_| game.en_passant_possible()) {
+        key ^= KEYS.en_passant[target.1];
+    }
+    key
+}
+
+/// Hashes `game` from scratch. Game::hash should always be equal to this.
+pub fn hash(game: &Game) -> u64 {
+    game.pieces()
+        .map(|piece| piece_key(&piece.side, piece.typ_index, piece::square_index(piece.position)))
+        .fold(state_key(game), |key, piece_key| key ^ piece_key)
+}
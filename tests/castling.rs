@@ -0,0 +1,71 @@
+use chess::{board_to_fen, fen_to_board, parse_movement, Game, MoveError};
+
+fn play(game: &mut Game, moves: &[&str]) {
+    for text in moves {
+        game.move_piece(&parse_movement(text).unwrap()).unwrap();
+    }
+}
+
+fn castle(fen: &str, movement: &str) -> Result<(), MoveError> {
+    fen_to_board(fen).unwrap().move_piece(&parse_movement(movement).unwrap())
+}
+
+#[test]
+fn the_rook_comes_along() {
+    let mut game = fen_to_board("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    play(&mut game, &["e1g1", "e8c8"]);
+    assert_eq!(board_to_fen(&game), "2kr3r/8/8/8/8/8/8/R4RK1 w - - 2 2");
+}
+
+#[test]
+fn not_through_pieces() {
+    assert_eq!(castle("4k3/8/8/8/8/8/8/R3KB1R w KQ - 0 1", "e1g1"), Err(MoveError::Illegal));
+    assert_eq!(castle("4k3/8/8/8/8/8/8/RN2K2R w KQ - 0 1", "e1c1"), Err(MoveError::Illegal));
+}
+
+#[test]
+fn not_out_of_check() {
+    assert_eq!(castle("4k3/4r3/8/8/8/8/8/R3K2R w KQ - 0 1", "e1g1"), Err(MoveError::Illegal));
+    assert_eq!(castle("4k3/4r3/8/8/8/8/8/R3K2R w KQ - 0 1", "e1c1"), Err(MoveError::Illegal));
+}
+
+#[test]
+fn not_through_an_attacked_square() {
+    assert_eq!(castle("4k3/5r2/8/8/8/8/8/R3K2R w KQ - 0 1", "e1g1"), Err(MoveError::Illegal));
+    assert_eq!(castle("4k3/3r4/8/8/8/8/8/R3K2R w KQ - 0 1", "e1c1"), Err(MoveError::Illegal));
+    // Only the king's path matters, the rook may cross b1 under attack
+    assert_eq!(castle("4k3/1r6/8/8/8/8/8/R3K2R w KQ - 0 1", "e1c1"), Ok(()));
+}
+
+#[test]
+fn not_into_check() {
+    assert_eq!(castle("4k3/6r1/8/8/8/8/8/R3K2R w KQ - 0 1", "e1g1"), Err(MoveError::Illegal));
+    assert_eq!(castle("4k3/6r1/8/8/8/8/8/R3K2R w KQ - 0 1", "e1c1"), Ok(()));
+}
+
+#[test]
+fn not_after_the_king_moved() {
+    let mut game = fen_to_board("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    play(&mut game, &["e1f1", "e8d8", "f1e1", "d8e8"]);
+    assert_eq!(board_to_fen(&game), "r3k2r/8/8/8/8/8/8/R3K2R w - - 4 3");
+    assert_eq!(game.move_piece(&parse_movement("e1g1").unwrap()), Err(MoveError::Illegal));
+    assert_eq!(game.move_piece(&parse_movement("e1c1").unwrap()), Err(MoveError::Illegal));
+}
+
+#[test]
+fn not_after_the_rook_moved() {
+    let mut game = fen_to_board("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    play(&mut game, &["h1h2", "a8a7", "h2h1", "a7a8"]);
+    assert_eq!(board_to_fen(&game), "r3k2r/8/8/8/8/8/8/R3K2R w Qk - 4 3");
+    assert_eq!(game.move_piece(&parse_movement("e1g1").unwrap()), Err(MoveError::Illegal));
+    // The other rook never moved
+    assert_eq!(game.move_piece(&parse_movement("e1c1").unwrap()), Ok(()));
+}
+
+#[test]
+fn not_after_the_rook_was_captured() {
+    let mut game = fen_to_board("r3k2r/8/8/8/8/8/6b1/R3K2R b KQkq - 0 1").unwrap();
+    play(&mut game, &["g2h1"]);
+    assert_eq!(board_to_fen(&game), "r3k2r/8/8/8/8/8/8/R3K2b w Qkq - 0 2");
+    assert_eq!(game.move_piece(&parse_movement("e1g1").unwrap()), Err(MoveError::Illegal));
+}
@@ -0,0 +1,35 @@
+use chess::{board_to_fen, fen, fen_to_board, parse_movement, Game, MoveError};
+
+fn play(game: &mut Game, moves: &[&str]) {
+    for text in moves {
+        game.move_piece(&parse_movement(text).unwrap()).unwrap();
+    }
+}
+
+#[test]
+fn allowed_right_after_the_double_push() {
+    let mut game = fen_to_board(fen::START_FEN).unwrap();
+    play(&mut game, &["e2e4", "a7a6", "e4e5", "d7d5"]);
+    assert_eq!(board_to_fen(&game), "rnbqkbnr/1pp1pppp/p7/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3");
+
+    let movement = game.legal_move(&parse_movement("e5d6").unwrap()).unwrap();
+    assert!(movement.flags.en_passant && movement.flags.capture);
+    play(&mut game, &["e5d6"]);
+    // The pawn on d5 is gone even though nothing landed on its square
+    assert_eq!(board_to_fen(&game), "rnbqkbnr/1pp1pppp/p2P4/8/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 3");
+}
+
+#[test]
+fn refused_one_ply_later() {
+    let mut game = fen_to_board(fen::START_FEN).unwrap();
+    play(&mut game, &["e2e4", "a7a6", "e4e5", "d7d5", "h2h3", "h7h6"]);
+    assert_eq!(game.en_passant, None);
+    assert_eq!(game.move_piece(&parse_movement("e5d6").unwrap()), Err(MoveError::Illegal));
+}
+
+#[test]
+fn read_from_fen() {
+    let mut game = fen_to_board("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1").unwrap();
+    play(&mut game, &["d4e3"]);
+    assert_eq!(board_to_fen(&game), "4k3/8/8/8/8/4p3/8/4K3 w - - 0 2");
+}
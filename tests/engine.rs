@@ -0,0 +1,51 @@
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+use chess::engine::{search, SearchInfo, SearchLimits};
+use chess::fen_to_board;
+use chess::transposition::TranspositionTable;
+
+fn best(fen: &str, depth: usize) -> (String, Vec<SearchInfo>) {
+    let game = fen_to_board(fen).unwrap();
+    let mut reports = vec![];
+    let limits = SearchLimits { depth: Some(depth), time: None };
+    let best = search(&game, &limits, &TranspositionTable::new(1), &AtomicBool::new(false), |info| reports.push(info.clone()));
+    (best.map(|movement| movement.to_string()).unwrap_or_default(), reports)
+}
+
+#[test]
+fn takes_a_free_queen() {
+    assert_eq!(best("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1", 2).0, "d1d5");
+}
+
+#[test]
+fn sees_the_recapture() {
+    // Qxd5 wins a rook at depth 1, but quiescence notices cxd5 takes the queen back
+    assert_ne!(best("4k3/8/2p5/3r4/8/8/8/3QK3 w - - 0 1", 1).0, "d1d5");
+}
+
+#[test]
+fn finds_mate_in_two() {
+    let (movement, reports) = best("2r3k1/5ppp/8/8/8/8/3R1PPP/3R2K1 w - - 0 1", 4);
+    assert_eq!(movement, "d2d8");
+    assert_eq!(reports.last().unwrap().mate_in(), Some(2));
+}
+
+#[test]
+fn reports_every_depth() {
+    let (_, reports) = best("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 3);
+    assert_eq!(reports.iter().map(|info| info.depth).collect::<Vec<_>>(), [1, 2, 3]);
+    assert!(reports.iter().all(|info| !info.pv.is_empty() && info.pv.len() <= info.depth));
+}
+
+#[test]
+fn always_has_a_move_when_stopped() {
+    let game = fen_to_board(chess::fen::START_FEN).unwrap();
+    let limits = SearchLimits { depth: None, time: Some(Duration::ZERO) };
+    assert!(search(&game, &limits, &TranspositionTable::new(1), &AtomicBool::new(true), |_| {}).is_some());
+}
+
+#[test]
+fn no_move_without_legal_moves() {
+    assert_eq!(best("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", 3).0, "");
+}
@@ -0,0 +1,64 @@
+use chess::eval::{evaluate, evaluation, PHASE_TOTAL};
+use chess::{fen, fen_to_board};
+
+// The same position with the colours swapped and the board turned around
+fn mirror(fen: &str) -> String {
+    let fields: Vec<&str> = fen.split(' ').collect();
+    let swap_case = |text: &str| -> String {
+        text.chars().map(|c| if c.is_uppercase() { c.to_ascii_lowercase() } else { c.to_ascii_uppercase() }).collect()
+    };
+    let placement: Vec<String> = fields[0].split('/').rev().map(swap_case).collect();
+    let side = if fields[1] == "w" { "b" } else { "w" };
+    format!("{} {} - - 0 1", placement.join("/"), side)
+}
+
+#[test]
+fn start_position_is_level() {
+    let game = fen_to_board(fen::START_FEN).unwrap();
+    assert_eq!(evaluation(&game).total(), 0);
+    assert_eq!(evaluation(&game).phase, PHASE_TOTAL);
+    assert_eq!(evaluate(&game), 0);
+}
+
+#[test]
+fn colours_are_treated_the_same() {
+    for fen in [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1",
+    ] {
+        let game = fen_to_board(fen).unwrap();
+        let mirrored = fen_to_board(&mirror(fen)).unwrap();
+        assert_eq!(evaluation(&game).total(), -evaluation(&mirrored).total(), "{}", fen);
+        assert_eq!(evaluate(&game), evaluate(&mirrored), "{}", fen);
+    }
+}
+
+#[test]
+fn scores_for_the_side_to_move() {
+    let white = fen_to_board("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+    let black = fen_to_board("4k3/8/8/8/8/8/8/3QK3 b - - 0 1").unwrap();
+    assert!(evaluate(&white) > 800);
+    assert_eq!(evaluate(&black), -evaluate(&white));
+}
+
+#[test]
+fn pawn_structure() {
+    let structure = |fen: &str| evaluation(&fen_to_board(fen).unwrap()).pawn_structure;
+    // Connected pawns against connected pawns
+    assert_eq!(structure("4k3/3pp3/8/8/8/8/3PP3/4K3 w - - 0 1"), 0);
+    // Doubled and isolated
+    assert!(structure("4k3/3pp3/8/8/8/4P3/4P3/4K3 w - - 0 1") < 0);
+    // A passed pawn, worth more the further it has gone
+    let passed = structure("4k3/8/8/8/8/8/P7/4K3 w - - 0 1");
+    assert!(structure("4k3/8/8/8/8/PP6/8/4K3 w - - 0 1") > 0);
+    assert!(structure("4k3/8/P7/8/8/8/8/4K3 w - - 0 1") > passed);
+}
+
+#[test]
+fn phase_goes_down_as_pieces_come_off() {
+    let phase = |fen: &str| evaluation(&fen_to_board(fen).unwrap()).phase;
+    assert_eq!(phase("4k3/pppppppp/8/8/8/8/PPPPPPPP/4K3 w - - 0 1"), 0);
+    assert_eq!(phase("3qk3/8/8/8/8/8/8/3QK3 w - - 0 1"), 8);
+}
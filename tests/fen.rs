@@ -0,0 +1,80 @@
+use chess::{board_to_fen, fen_to_board, FenError};
+
+#[test]
+fn round_trips() {
+    for fen in [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b Kq - 3 17",
+        "8/8/8/8/8/8/8/K6k w - - 99 120",
+    ] {
+        assert_eq!(board_to_fen(&fen_to_board(fen).unwrap()), fen);
+    }
+}
+
+fn error(fen: &str) -> FenError {
+    fen_to_board(fen).expect_err("FEN should be rejected")
+}
+
+#[test]
+fn reports_bad_pieces() {
+    assert_eq!(error("rnbqkbnr/ppxppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"), FenError::InvalidPiece { column: 11, found: 'x' });
+}
+
+#[test]
+fn reports_ranks_of_the_wrong_length() {
+    assert_eq!(error("rnbqkbnr/ppppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"), FenError::RankOverflow { column: 17 });
+    assert_eq!(error("rnbqkbnr/pppppppp/45/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"), FenError::RankOverflow { column: 19 });
+    assert_eq!(error("rnbqkbnr/ppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"), FenError::RankUnderflow { column: 16 });
+}
+
+#[test]
+fn reports_the_wrong_number_of_ranks() {
+    assert_eq!(error("8/8/8/8/8/8/8 w - - 0 1"), FenError::WrongNumberOfRanks { column: 13, found: 7 });
+    assert_eq!(error("8/8/8/8/8/8/8/8/8 w - - 0 1"), FenError::WrongNumberOfRanks { column: 15, found: 9 });
+}
+
+#[test]
+fn reports_a_bad_side() {
+    assert_eq!(error("8/8/8/8/8/8/8/K6k x - - 0 1"), FenError::InvalidSide { column: 18 });
+    // The doubled space leaves an empty side field, not a seventh field
+    assert_eq!(error("8/8/8/8/8/8/8/K6k  w - - 0 1"), FenError::InvalidSide { column: 17 });
+}
+
+#[test]
+fn reports_bad_castling() {
+    assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQxq - 0 1"), FenError::InvalidCastling { column: 48 });
+    assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w QK - 0 1"), FenError::InvalidCastling { column: 47 });
+}
+
+#[test]
+fn reports_a_bad_en_passant_square() {
+    assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e5 0 1"), FenError::InvalidEnPassant { column: 51 });
+    // No pawn in front of the square, only a knight
+    assert_eq!(error("4k3/8/8/8/3pN3/8/8/4K3 b - e3 0 1"), FenError::InvalidEnPassant { column: 27 });
+    // The square itself is taken
+    assert_eq!(error("4k3/8/8/8/3pP3/4N3/8/4K3 b - e3 0 1"), FenError::InvalidEnPassant { column: 29 });
+    // So is the square the pawn came from
+    assert_eq!(error("4k3/8/8/8/3pP3/8/4N3/4K3 b - e3 0 1"), FenError::InvalidEnPassant { column: 29 });
+}
+
+#[test]
+fn drops_castling_rights_without_the_king_and_rook_at_home() {
+    let game = fen_to_board("4k3/8/8/8/8/8/8/4K3 w KQkq - 0 1").unwrap();
+    assert_eq!(board_to_fen(&game), "4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+    assert_eq!(game.hash, fen_to_board("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap().hash);
+
+    let game = fen_to_board("r3k3/8/8/8/8/8/8/4K2R w KQkq - 0 1").unwrap();
+    assert_eq!(board_to_fen(&game), "r3k3/8/8/8/8/8/8/4K2R w Kq - 0 1");
+}
+
+#[test]
+fn reports_bad_clocks() {
+    assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - -1 1"), FenError::InvalidHalfmoveClock { column: 53 });
+    assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 0"), FenError::InvalidFullmoveNumber { column: 55 });
+}
+
+#[test]
+fn reports_a_seventh_field_instead_of_crashing() {
+    assert_eq!(error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 extra"), FenError::TooManyFields { column: 57 });
+}
@@ -0,0 +1,62 @@
+use chess::history::History;
+use chess::{board_to_fen, fen, fen_to_board, san_to_move, Game};
+
+fn play(history: &mut History, game: &mut Game, moves: &[&str]) {
+    for san in moves {
+        let movement = san_to_move(game, san).unwrap();
+        history.play(game, movement);
+    }
+}
+
+#[test]
+fn undo_and_redo_restore_the_fen() {
+    let mut game = fen_to_board(fen::START_FEN).unwrap();
+    let mut history = History::new();
+    play(&mut history, &mut game, &["e4", "e5", "Nf3"]);
+    let after = board_to_fen(&game);
+
+    assert!(history.undo(&mut game).is_some());
+    assert_eq!(board_to_fen(&game), "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2");
+    history.undo(&mut game);
+    history.undo(&mut game);
+    assert_eq!(board_to_fen(&game), fen::START_FEN);
+    assert!(history.undo(&mut game).is_none());
+    assert!(history.is_empty());
+
+    while history.redo(&mut game).is_some() {}
+    assert_eq!(board_to_fen(&game), after);
+    assert_eq!(history.moves().len(), 3);
+}
+
+#[test]
+fn a_new_move_clears_redo() {
+    let mut game = fen_to_board(fen::START_FEN).unwrap();
+    let mut history = History::new();
+    play(&mut history, &mut game, &["e4", "e5"]);
+    history.undo(&mut game);
+    play(&mut history, &mut game, &["c5"]);
+
+    assert!(history.redo(&mut game).is_none());
+    assert_eq!(history.lines(&game), ["1. e4 c5"]);
+}
+
+#[test]
+fn start_is_the_position_before_the_first_move() {
+    let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+    let mut game = fen_to_board(fen).unwrap();
+    let mut history = History::new();
+    play(&mut history, &mut game, &["Bb5", "a6"]);
+
+    assert_eq!(board_to_fen(&history.start(&game)), fen);
+}
+
+#[test]
+fn numbering_starts_from_the_fen_when_black_moves_first() {
+    let mut game = fen_to_board("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 12").unwrap();
+    let mut history = History::new();
+    play(&mut history, &mut game, &["e5"]);
+    assert_eq!(history.lines(&game), ["12... e5"]);
+
+    play(&mut history, &mut game, &["Nf3", "Nc6", "Bb5"]);
+    assert_eq!(history.lines(&game), ["12... e5", "13. Nf3 Nc6", "14. Bb5"]);
+}
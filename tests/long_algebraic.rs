@@ -0,0 +1,30 @@
+use chess::{fen, fen_to_board, parse_movement, Move, ParseMoveError};
+
+#[test]
+fn reads_uci_moves() {
+    assert_eq!(parse_movement("e2e4"), Ok(Move::new((6, 4), (4, 4), None)));
+    assert_eq!(parse_movement("e7e8q"), Ok(Move::new((1, 4), (0, 4), Some(4))));
+    assert_eq!(parse_movement("e1g1"), Ok(Move::new((7, 4), (7, 6), None)));
+    assert_eq!(parse_movement("  E7E8N\n"), Ok(Move::new((1, 4), (0, 4), Some(2))));
+    assert_eq!(parse_movement("e2 e4"), Ok(Move::new((6, 4), (4, 4), None)));
+}
+
+#[test]
+fn writes_what_it_reads() {
+    let game = fen_to_board("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1").unwrap();
+    for movement in game.legal_moves() {
+        let parsed = parse_movement(&movement.to_string()).unwrap();
+        assert_eq!(game.legal_move(&parsed), Ok(movement));
+    }
+    let game = fen_to_board(fen::START_FEN).unwrap();
+    assert!(game.legal_moves().iter().any(|movement| movement.to_string() == "g1f3"));
+}
+
+#[test]
+fn says_why_it_failed() {
+    assert_eq!(parse_movement(""), Err(ParseMoveError::WrongLength { found: 0 }));
+    assert_eq!(parse_movement("e2e4e5"), Err(ParseMoveError::WrongLength { found: 6 }));
+    assert_eq!(parse_movement("i2e4"), Err(ParseMoveError::InvalidFile { found: 'i' }));
+    assert_eq!(parse_movement("e2e9"), Err(ParseMoveError::InvalidRank { found: '9' }));
+    assert_eq!(parse_movement("e7e8k"), Err(ParseMoveError::InvalidPromotion { found: 'k' }));
+}
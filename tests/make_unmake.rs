@@ -0,0 +1,79 @@
+use chess::{board_to_fen, fen_to_board, Game, Move};
+
+const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+fn find(game: &Game, from: &str, to: &str) -> Move {
+    let square = |name: &str| {
+        let bytes = name.as_bytes();
+        ((b'8' - bytes[1]) as usize, (bytes[0] - b'a') as usize)
+    };
+    *game.legal_moves()
+        .iter()
+        .find(|movement| movement.from == square(from) && movement.to == square(to))
+        .expect("move should be legal")
+}
+
+// Plays every legal move two plies deep and checks taking it back leaves
+// nothing behind
+fn round_trip(game: &mut Game, depth: usize) {
+    if depth == 0 {
+        return;
+    }
+    let before = game.clone();
+    for movement in game.legal_moves() {
+        let undo = game.make_move(movement);
+        round_trip(game, depth - 1);
+        game.unmake_move(undo);
+        assert_eq!(board_to_fen(game), board_to_fen(&before), "after taking back {}", movement);
+        assert_eq!(game.board, before.board);
+        assert_eq!(game.bitboards, before.bitboards);
+        assert_eq!(game.hash, before.hash);
+        assert_eq!(game.position_history, before.position_history);
+    }
+}
+
+#[test]
+fn unmake_restores_every_position() {
+    for fen in [
+        KIWIPETE,
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    ] {
+        round_trip(&mut fen_to_board(fen).unwrap(), 2);
+    }
+}
+
+#[test]
+fn flags_describe_the_move() {
+    let game = fen_to_board(KIWIPETE).unwrap();
+    let castle = find(&game, "e1", "g1");
+    assert!(castle.flags.castle && !castle.flags.capture);
+    let capture = find(&game, "e5", "f7");
+    assert!(capture.flags.capture && !capture.flags.castle);
+    let double_push = find(&game, "a2", "a4");
+    assert!(double_push.flags.double_push);
+
+    let game = fen_to_board("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3").unwrap();
+    let en_passant = find(&game, "e5", "f6");
+    assert!(en_passant.flags.en_passant && en_passant.flags.capture);
+}
+
+#[test]
+fn undo_restores_captures_and_state() {
+    let mut game = fen_to_board("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3").unwrap();
+    let fen = board_to_fen(&game);
+    let undo = game.make_move(find(&game, "e5", "f6"));
+    assert_eq!(undo.captured.map(|pawn| pawn.position), Some((3, 5)));
+    assert_eq!(board_to_fen(&game), "rnbqkbnr/ppp1p1pp/5P2/3p4/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 3");
+    game.unmake_move(undo);
+    assert_eq!(board_to_fen(&game), fen);
+
+    // Promoting with a capture takes away a castling right, which has to come back
+    let mut game = fen_to_board("r3k2r/1P6/8/8/8/8/8/4K3 w kq - 3 40").unwrap();
+    let fen = board_to_fen(&game);
+    let promotion = *game.legal_moves().iter().find(|movement| movement.to == (0, 0) && movement.promotion == Some(2)).unwrap();
+    let undo = game.make_move(promotion);
+    assert_eq!(board_to_fen(&game), "N3k2r/8/8/8/8/8/8/4K3 b k - 0 40");
+    game.unmake_move(undo);
+    assert_eq!(board_to_fen(&game), fen);
+}
@@ -0,0 +1,82 @@
+// Node counts for the standard perft positions, from
+// https://www.chessprogramming.org/Perft_Results
+
+use chess::{board_to_fen, fen, fen_to_board, perft::{divide, perft}, Game};
+
+fn check(fen: &str, expected: &[u64]) {
+    let game = fen_to_board(fen).unwrap();
+    for (depth, nodes) in expected.iter().enumerate() {
+        assert_eq!(perft(&game, depth + 1), *nodes, "depth {} of {}", depth + 1, fen);
+    }
+}
+
+#[test]
+fn start_position() {
+    check(fen::START_FEN, &[20, 400, 8902, 197281]);
+}
+
+#[test]
+fn kiwipete() {
+    check("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", &[48, 2039, 97862]);
+}
+
+#[test]
+fn position_3() {
+    check("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", &[14, 191, 2812, 43238, 674624]);
+}
+
+#[test]
+fn position_4() {
+    check("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", &[6, 264, 9467, 422333]);
+}
+
+#[test]
+fn position_4_mirrored() {
+    check("r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1", &[6, 264, 9467, 422333]);
+}
+
+#[test]
+fn position_5() {
+    check("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", &[44, 1486, 62379]);
+}
+
+#[test]
+fn position_6() {
+    check("r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10", &[46, 2079, 89890]);
+}
+
+#[test]
+fn divide_adds_up_to_perft() {
+    let game = fen_to_board("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+    let split = divide(&game, 2);
+    assert_eq!(split.len(), 48);
+    assert_eq!(split.iter().map(|(_, nodes)| nodes).sum::<u64>(), 2039);
+}
+
+// Two plies into each position, the captures are exactly the legal moves
+// that take something or promote, in the same order
+fn check_captures(game: &mut Game, depth: usize) {
+    let mut expected = game.legal_moves();
+    expected.retain(|movement| movement.flags.capture || movement.promotion.is_some());
+    assert_eq!(game.legal_captures(), expected, "captures in {}", board_to_fen(game));
+    if depth == 0 {
+        return;
+    }
+    for movement in game.legal_moves() {
+        let undo = game.make_move(movement);
+        check_captures(game, depth - 1);
+        game.unmake_move(undo);
+    }
+}
+
+#[test]
+fn legal_captures_match_legal_moves() {
+    for fen in [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    ] {
+        check_captures(&mut fen_to_board(fen).unwrap(), 2);
+    }
+}
@@ -0,0 +1,154 @@
+use chess::pgn::{game_to_pgn, games, PgnError, PgnGame};
+use chess::{board_to_fen, fen, fen_to_board, san_to_move, SanError};
+
+const TWO_GAMES: &str = r#"[Event "Casual"]
+[White "Alice"]
+[Black "Bob \"the Bishop\""]
+[Result "1-0"]
+
+1. e4 {best by test} e5 2. Nf3 (2. f4 exf4 (2... d5) 3. Nf3) Nc6 $1 3.Bb5 a6!? 4. Bxc6 dxc6 ; the exchange
+5. O-O 1-0
+
+[Event "Endgame"]
+[SetUp "1"]
+[FEN "4k3/8/8/8/8/8/4P3/4K3 b - - 0 60"]
+
+60... Kd7 61. e4 *
+"#;
+
+#[test]
+fn reads_every_game() {
+    let games: Vec<_> = games(TWO_GAMES).collect::<Result<_, _>>().unwrap();
+    assert_eq!(games.len(), 2);
+
+    assert_eq!(games[0].tag("White"), Some("Alice"));
+    assert_eq!(games[0].tag("Black"), Some("Bob \"the Bishop\""));
+    assert_eq!(games[0].tag("Round"), None);
+    assert_eq!(games[0].moves.len(), 9);
+    assert_eq!(games[0].result.as_deref(), Some("1-0"));
+    assert_eq!(board_to_fen(&games[0].final_game()), "r1bqkbnr/1pp2ppp/p1p5/4p3/4P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 1 5");
+
+    assert_eq!(board_to_fen(&games[1].start), "4k3/8/8/8/8/8/4P3/4K3 b - - 0 60");
+    assert_eq!(board_to_fen(&games[1].final_game()), "8/3k4/8/8/4P3/8/8/4K3 b - e3 0 61");
+    assert_eq!(games[1].result.as_deref(), Some("*"));
+}
+
+#[test]
+fn game_at_any_ply() {
+    let game = games(TWO_GAMES).next().unwrap().unwrap();
+    assert_eq!(board_to_fen(&game.game_at(0).unwrap()), fen::START_FEN);
+    assert_eq!(board_to_fen(&game.game_at(3).unwrap()), "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+    assert!(game.game_at(9).is_some());
+    assert!(game.game_at(10).is_none());
+}
+
+#[test]
+fn reports_bad_games_and_carries_on() {
+    let text = "[Event \"Bad\"]\n\n1. e4 e5 2. Ke3 *\n\n[Event \"Good\"]\n\n1. d4 *\n";
+    let mut games = games(text);
+    assert_eq!(games.next().unwrap().unwrap_err(), PgnError::IllegalMove {
+        line: 3,
+        san: "Ke3".to_string(),
+        error: SanError::NoSuchMove,
+    });
+    assert_eq!(games.next().unwrap().unwrap().tag("Event"), Some("Good"));
+    assert!(games.next().is_none());
+}
+
+#[test]
+fn reports_broken_syntax() {
+    assert_eq!(games("[Event Bad]\n1. e4 *").next().unwrap().unwrap_err(), PgnError::InvalidTag { line: 1 });
+    assert_eq!(games("1. e4 {never closed\n").next().unwrap().unwrap_err(), PgnError::UnclosedComment { line: 1 });
+    assert_eq!(games("1. e4 (1. d4\n*").next().unwrap().unwrap_err(), PgnError::UnbalancedVariation { line: 1 });
+    assert!(matches!(
+        games("[FEN \"8/8/8\"]\n*").next().unwrap().unwrap_err(),
+        PgnError::InvalidFen { line: 1, .. }
+    ));
+}
+
+#[test]
+fn empty_text_has_no_games() {
+    assert!(games("").next().is_none());
+    assert!(games("\n  \n").next().is_none());
+}
+
+#[test]
+fn writes_what_it_reads() {
+    for game in games(TWO_GAMES) {
+        let game = game.unwrap();
+        let written = game_to_pgn(&game);
+        let again = games(&written).next().unwrap().unwrap();
+        assert_eq!(again.moves, game.moves);
+        assert_eq!(again.result, game.result);
+        assert_eq!(again.tag("Event"), game.tag("Event"));
+        assert_eq!(game_to_pgn(&again), written);
+    }
+}
+
+#[test]
+fn keeps_the_result_until_the_moves_change() {
+    let loaded = games("[Event \"Casual\"]\n[Result \"1-0\"]\n\n1. e4 e5 1-0\n").next().unwrap().unwrap();
+    let written = game_to_pgn(&loaded.with_moves(loaded.moves.clone()));
+    assert!(written.contains("[Event \"Casual\"]"));
+    assert!(written.contains("[Result \"1-0\"]"));
+    assert!(written.trim_end().ends_with("1. e4 e5 1-0"));
+
+    // Another move and the game is still going
+    let mut moves = loaded.moves.clone();
+    moves.push(san_to_move(&loaded.final_game(), "Nf3").unwrap());
+    let written = game_to_pgn(&loaded.with_moves(moves));
+    assert!(written.contains("[Event \"Casual\"]"));
+    assert!(written.contains("[Result \"*\"]"));
+    assert!(written.trim_end().ends_with("2. Nf3 *"));
+}
+
+#[test]
+fn writes_the_seven_tag_roster_and_setup() {
+    let start = fen_to_board("4k3/8/8/8/8/8/4P3/4K3 b - - 0 60").unwrap();
+    let mut game = start.clone();
+    let mut moves = vec![];
+    for san in ["Kd7", "e4"] {
+        let movement = san_to_move(&game, san).unwrap();
+        game.make_move(movement);
+        moves.push(movement);
+    }
+    let mut record = PgnGame::new(start, moves);
+    record.tags.push(("White".to_string(), "Alice".to_string()));
+    record.tags.push(("Annotator".to_string(), "Bob".to_string()));
+    assert_eq!(game_to_pgn(&record), concat!(
+        "[Event \"?\"]\n",
+        "[Site \"?\"]\n",
+        "[Date \"????.??.??\"]\n",
+        "[Round \"?\"]\n",
+        "[White \"Alice\"]\n",
+        "[Black \"?\"]\n",
+        "[Result \"*\"]\n",
+        "[SetUp \"1\"]\n",
+        "[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 60\"]\n",
+        "[Annotator \"Bob\"]\n",
+        "\n",
+        "60... Kd7 61. e4 *\n",
+    ));
+}
+
+#[test]
+fn wraps_movetext_at_80_columns() {
+    // Knights out and back again, long enough to need a few lines
+    let mut game = fen_to_board(fen::START_FEN).unwrap();
+    let mut moves = vec![];
+    for _ in 0..10 {
+        for san in ["Nf3", "Nf6", "Ng1", "Ng8"] {
+            let movement = san_to_move(&game, san).unwrap();
+            game.make_move(movement);
+            moves.push(movement);
+        }
+    }
+    let record = PgnGame::new(fen_to_board(fen::START_FEN).unwrap(), moves);
+    let written = game_to_pgn(&record);
+    let movetext: Vec<&str> = written.split("\n\n").nth(1).unwrap().lines().collect();
+    assert!(movetext.len() > 1);
+    assert!(movetext.iter().all(|line| line.len() <= 80 && !line.ends_with(' ')));
+    // Repeating the position five times is a draw
+    assert_eq!(record.result.as_deref(), Some("1/2-1/2"));
+    assert!(movetext.last().unwrap().ends_with("1/2-1/2"));
+}
@@ -0,0 +1,104 @@
+use chess::polyglot::{decode_move, key, Book, BookError, Selection};
+use chess::{fen, fen_to_board, parse_movement, Game};
+
+// Polyglot's move encoding for a move like "e2e4", promotion piece 1-4 for knight to queen
+fn encode(text: &str, promotion: u16) -> u16 {
+    let bytes = text.as_bytes();
+    let file = |index: usize| (bytes[index] - b'a') as u16;
+    let rank = |index: usize| (bytes[index] - b'1') as u16;
+    file(2) | rank(3) << 3 | file(0) << 6 | rank(1) << 9 | promotion << 12
+}
+
+fn book_bytes(entries: &[(u64, u16, u16)]) -> Vec<u8> {
+    let mut bytes = vec![];
+    for (key, movement, weight) in entries {
+        bytes.extend(key.to_be_bytes());
+        bytes.extend(movement.to_be_bytes());
+        bytes.extend(weight.to_be_bytes());
+        bytes.extend(0u32.to_be_bytes());
+    }
+    bytes
+}
+
+fn after(moves: &[&str]) -> Game {
+    let mut game = fen_to_board(fen::START_FEN).unwrap();
+    for text in moves {
+        game.move_piece(&parse_movement(text).unwrap()).unwrap();
+    }
+    game
+}
+
+#[test]
+fn keys_match_the_polyglot_reference() {
+    // The test positions from the Polyglot book format description
+    for (moves, expected) in [
+        (&[][..], 0x463b96181691fc9c),
+        (&["e2e4"][..], 0x823c9b50fd114196),
+        (&["e2e4", "d7d5"][..], 0x0756b94461c50fb0),
+        (&["e2e4", "d7d5", "e4e5"][..], 0x662fafb965db29d4),
+        (&["e2e4", "d7d5", "e4e5", "f7f5"][..], 0x22a48b5a8e47ff78),
+        (&["e2e4", "d7d5", "e4e5", "f7f5", "e1e2"][..], 0x652a607ca3f242c1),
+        (&["e2e4", "d7d5", "e4e5", "f7f5", "e1e2", "e8f7"][..], 0x00fdd303c946bdd9),
+        (&["a2a4", "b7b5", "h2h4", "b5b4", "c2c4"][..], 0x3c8123ea7b067637),
+        (&["a2a4", "b7b5", "h2h4", "b5b4", "c2c4", "b4c3", "a1a3"][..], 0x5c3f9b829b279560),
+    ] {
+        assert_eq!(key(&after(moves)), expected, "after {:?}", moves);
+    }
+}
+
+#[test]
+fn finds_the_moves_for_a_position() {
+    let start = after(&[]);
+    let e4 = after(&["e2e4"]);
+    let mut entries = vec![
+        (key(&start), encode("e2e4", 0), 10),
+        (key(&start), encode("d2d4", 0), 5),
+        (key(&start), encode("e2e5", 0), 50), // not legal, so left out
+        (key(&e4), encode("c7c5", 0), 1),
+        (1, encode("a2a3", 0), 1),
+        (u64::MAX, encode("h2h3", 0), 1),
+    ];
+    entries.sort_by_key(|entry| entry.0);
+    let book = Book::from_bytes(&book_bytes(&entries)).unwrap();
+    assert_eq!(book.len(), 6);
+    assert_eq!(book.entries(key(&start)).len(), 3);
+
+    let moves: Vec<(String, u16)> = book.moves(&start).iter().map(|(movement, weight)| (movement.to_string(), *weight)).collect();
+    assert_eq!(moves, [("e2e4".to_string(), 10), ("d2d4".to_string(), 5)]);
+    assert_eq!(book.choose(&start, Selection::Best).unwrap().to_string(), "e2e4");
+    assert_eq!(book.choose(&e4, Selection::Weighted).unwrap().to_string(), "c7c5");
+    assert_eq!(book.choose(&after(&["d2d4"]), Selection::Best), None);
+}
+
+#[test]
+fn weighted_choice_skips_moves_without_weight() {
+    let start = after(&[]);
+    let book = Book::from_bytes(&book_bytes(&[(key(&start), encode("e2e4", 0), 1), (key(&start), encode("d2d4", 0), 0), (key(&start), encode("c2c4", 0), 3)])).unwrap();
+    for _ in 0..50 {
+        let movement = book.choose(&start, Selection::Weighted).unwrap().to_string();
+        assert!(movement == "e2e4" || movement == "c2c4", "{}", movement);
+    }
+}
+
+#[test]
+fn decodes_castling_and_promotion() {
+    let game = fen_to_board("r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    let short = decode_move(&game, encode("e1h1", 0)).unwrap();
+    assert_eq!(short.to_string(), "e1g1");
+    assert!(short.flags.castle);
+    assert_eq!(decode_move(&game, encode("e1a1", 0)).unwrap().to_string(), "e1c1");
+    assert_eq!(decode_move(&game, encode("b7a8", 1)).unwrap().to_string(), "b7a8n");
+    assert_eq!(decode_move(&game, encode("b7b8", 4)).unwrap().to_string(), "b7b8q");
+
+    let black = fen_to_board("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1").unwrap();
+    assert_eq!(decode_move(&black, encode("e8h8", 0)).unwrap().to_string(), "e8g8");
+    assert_eq!(decode_move(&black, encode("e8a8", 0)).unwrap().to_string(), "e8c8");
+}
+
+#[test]
+fn rejects_broken_books() {
+    assert_eq!(Book::from_bytes(&[0; 20]).unwrap_err(), BookError::WrongLength { length: 20 });
+    let bytes = book_bytes(&[(1, 0, 0), (3, 0, 0), (2, 0, 0)]);
+    assert_eq!(Book::from_bytes(&bytes).unwrap_err(), BookError::Unsorted { entry: 2 });
+    assert!(Book::from_bytes(&[]).unwrap().is_empty());
+}
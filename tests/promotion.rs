@@ -0,0 +1,41 @@
+use chess::{board_to_fen, fen, fen_to_board, parse_movement, Game, MoveError};
+
+const PROMOTING: &str = "1r2k3/P7/8/8/8/8/8/4K3 w - - 3 40";
+
+fn after(fen: &str, movement: &str) -> Game {
+    let mut game = fen_to_board(fen).unwrap();
+    game.move_piece(&parse_movement(movement).unwrap()).unwrap();
+    game
+}
+
+#[test]
+fn under_promotion() {
+    assert_eq!(board_to_fen(&after(PROMOTING, "a7a8n")), "Nr2k3/8/8/8/8/8/8/4K3 b - - 0 40");
+    assert_eq!(board_to_fen(&after(PROMOTING, "a7 a8b")), "Br2k3/8/8/8/8/8/8/4K3 b - - 0 40");
+    assert_eq!(board_to_fen(&after("4k3/8/8/8/8/8/4p3/K7 b - - 0 1", "e2e1r")), "4k3/8/8/8/8/8/8/K3r3 w - - 0 2");
+}
+
+#[test]
+fn queen_unless_told_otherwise() {
+    assert_eq!(board_to_fen(&after(PROMOTING, "a7a8")), "Qr2k3/8/8/8/8/8/8/4K3 b - - 0 40");
+}
+
+#[test]
+fn promotion_by_capture() {
+    let game = fen_to_board(PROMOTING).unwrap();
+    let movement = game.legal_move(&parse_movement("a7b8n").unwrap()).unwrap();
+    assert!(movement.flags.capture);
+    assert_eq!(board_to_fen(&after(PROMOTING, "a7b8n")), "1N2k3/8/8/8/8/8/8/4K3 b - - 0 40");
+    assert_eq!(board_to_fen(&after(PROMOTING, "a7b8q")), "1Q2k3/8/8/8/8/8/8/4K3 b - - 0 40");
+}
+
+#[test]
+fn only_pawns_reaching_the_last_rank_promote() {
+    let mut game = fen_to_board(fen::START_FEN).unwrap();
+    assert_eq!(game.move_piece(&parse_movement("e2e4q").unwrap()), Err(MoveError::UnexpectedPromotion));
+    assert_eq!(game.move_piece(&parse_movement("g1f3n").unwrap()), Err(MoveError::UnexpectedPromotion));
+    // A piece other than a pawn reaching the last rank doesn't promote either
+    let mut game = fen_to_board("4k3/8/8/8/8/8/R7/4K3 w - - 0 1").unwrap();
+    assert_eq!(game.move_piece(&parse_movement("a2a8q").unwrap()), Err(MoveError::UnexpectedPromotion));
+    assert_eq!(board_to_fen(&game), "4k3/8/8/8/8/8/R7/4K3 w - - 0 1");
+}
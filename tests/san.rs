@@ -0,0 +1,62 @@
+use chess::{fen, fen_to_board, move_to_san, san_to_move, Game, SanError};
+
+fn san(fen: &str, movement: &str) -> String {
+    let game = fen_to_board(fen).unwrap();
+    let movement = san_to_move(&game, movement).unwrap();
+    move_to_san(&game, &movement)
+}
+
+// Every legal move written out and read back must be the same move
+fn round_trip(game: &Game) {
+    for movement in game.legal_moves() {
+        let written = move_to_san(game, &movement);
+        assert_eq!(san_to_move(game, &written), Ok(movement), "{}", written);
+    }
+}
+
+#[test]
+fn every_move_round_trips() {
+    for fen in [
+        fen::START_FEN,
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        "4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1",
+    ] {
+        round_trip(&fen_to_board(fen).unwrap());
+    }
+}
+
+#[test]
+fn writes_standard_san() {
+    assert_eq!(san(fen::START_FEN, "Nf3"), "Nf3");
+    assert_eq!(san(fen::START_FEN, "e4"), "e4");
+    assert_eq!(san("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2", "exd5"), "exd5");
+    assert_eq!(san("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "O-O"), "O-O");
+    assert_eq!(san("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", "0-0-0"), "O-O-O");
+    assert_eq!(san("8/4P3/8/7k/8/8/8/4K3 w - - 0 1", "e8=Q"), "e8=Q+");
+    assert_eq!(san("8/4P3/8/7k/8/8/8/4K3 w - - 0 1", "e8N"), "e8=N");
+    assert_eq!(san("3r2k1/8/8/8/8/8/5PPP/r2Q2K1 b - - 0 1", "Raxd1"), "Raxd1#");
+    assert_eq!(san("3r2k1/8/8/8/8/8/5PPP/r2Q2K1 b - - 0 1", "Rdd1"), "Rdxd1#");
+}
+
+#[test]
+fn disambiguates_by_file_rank_or_square() {
+    let knights = "4k3/8/8/6N1/8/8/8/4K1N1 w - - 0 1";
+    assert_eq!(san(knights, "N1f3"), "N1f3");
+    assert_eq!(san(knights, "Ng5f3"), "N5f3");
+    assert_eq!(san_to_move(&fen_to_board(knights).unwrap(), "Nf3"), Err(SanError::Ambiguous));
+
+    let queens = "4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1";
+    assert_eq!(san(queens, "Qa1b2"), "Qa1b2");
+    assert_eq!(san(queens, "Qcb2"), "Qcb2");
+}
+
+#[test]
+fn rejects_bad_input() {
+    let game = fen_to_board(fen::START_FEN).unwrap();
+    assert_eq!(san_to_move(&game, ""), Err(SanError::Empty));
+    assert_eq!(san_to_move(&game, "Zf3"), Err(SanError::Invalid));
+    assert_eq!(san_to_move(&game, "e5"), Err(SanError::NoSuchMove));
+    assert_eq!(san_to_move(&game, "O-O"), Err(SanError::NoSuchMove));
+}
@@ -0,0 +1,113 @@
+use chess::{fen, fen_to_board, parse_movement, DrawReason, Game, GameStatus, Side};
+
+fn play(game: &mut Game, moves: &[&str]) {
+    for text in moves {
+        game.move_piece(&parse_movement(text).unwrap()).unwrap();
+    }
+}
+
+fn status(fen: &str) -> GameStatus {
+    fen_to_board(fen).unwrap().status()
+}
+
+fn draw(reason: DrawReason) -> GameStatus {
+    GameStatus::Draw { reason }
+}
+
+#[test]
+fn the_start_is_ongoing() {
+    assert_eq!(status(fen::START_FEN), GameStatus::Ongoing);
+}
+
+#[test]
+fn checkmate_names_the_winner() {
+    // Fool's mate
+    let mut game = fen_to_board(fen::START_FEN).unwrap();
+    play(&mut game, &["f2f3", "e7e5", "g2g4", "d8h4"]);
+    assert_eq!(game.status(), GameStatus::Checkmate { winner: Side::Black });
+
+    // Back rank mate
+    assert_eq!(status("6k1/5ppp/8/8/8/8/8/R5K1 b - - 0 1"), GameStatus::Ongoing);
+    assert_eq!(status("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1"), GameStatus::Checkmate { winner: Side::White });
+}
+
+#[test]
+fn stalemate_is_a_draw() {
+    assert_eq!(status("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"), draw(DrawReason::Stalemate));
+    // The same position with white to move isn't over
+    assert_eq!(status("7k/5Q2/6K1/8/8/8/8/8 w - - 0 1"), GameStatus::Ongoing);
+}
+
+#[test]
+fn fifty_moves_can_be_claimed() {
+    let mut game = fen_to_board("4k3/8/8/8/8/8/8/R3K3 w - - 99 80").unwrap();
+    assert_eq!(game.claimable_draw(), None);
+    play(&mut game, &["a1a2"]);
+    assert_eq!(game.halfmove_clock, 100);
+    assert_eq!(game.claimable_draw(), Some(DrawReason::FiftyMoveRule));
+    // Only claimable, the game goes on until someone does
+    assert_eq!(game.status(), GameStatus::Ongoing);
+
+    // A pawn move starts the count again
+    let mut game = fen_to_board("4k3/8/8/8/8/8/P7/4K3 w - - 99 80").unwrap();
+    play(&mut game, &["a2a3"]);
+    assert_eq!(game.claimable_draw(), None);
+}
+
+#[test]
+fn seventy_five_moves_end_the_game() {
+    let mut game = fen_to_board("4k3/8/8/8/8/8/8/R3K3 w - - 149 105").unwrap();
+    assert_eq!(game.status(), GameStatus::Ongoing);
+    play(&mut game, &["a1a2"]);
+    assert_eq!(game.halfmove_clock, 150);
+    assert_eq!(game.status(), draw(DrawReason::SeventyFiveMoveRule));
+
+    // Mate on the move that reaches 150 still counts as mate
+    let mut game = fen_to_board("6k1/5ppp/8/8/8/8/8/R5K1 w - - 149 105").unwrap();
+    play(&mut game, &["a1a8"]);
+    assert_eq!(game.status(), GameStatus::Checkmate { winner: Side::White });
+}
+
+#[test]
+fn repetition() {
+    let mut game = fen_to_board(fen::START_FEN).unwrap();
+    let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+    play(&mut game, &shuffle);
+    assert_eq!(game.repetitions(), 2);
+    assert_eq!(game.claimable_draw(), None);
+
+    play(&mut game, &shuffle);
+    assert_eq!(game.repetitions(), 3);
+    assert_eq!(game.claimable_draw(), Some(DrawReason::ThreefoldRepetition));
+    assert_eq!(game.status(), GameStatus::Ongoing);
+
+    play(&mut game, &shuffle);
+    assert_eq!(game.status(), GameStatus::Ongoing);
+    play(&mut game, &shuffle);
+    assert_eq!(game.repetitions(), 5);
+    assert_eq!(game.status(), draw(DrawReason::FivefoldRepetition));
+}
+
+#[test]
+fn insufficient_material() {
+    for fen in [
+        "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+        "4k3/8/8/8/8/8/8/4KN2 w - - 0 1",
+        "4k3/8/8/8/8/8/8/4KB2 b - - 0 1",
+        // Bishops on c1 and f8 are both on dark squares
+        "4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1",
+        "4k3/8/8/8/8/8/1B6/2B1K3 w - - 0 1",
+    ] {
+        assert_eq!(status(fen), draw(DrawReason::InsufficientMaterial), "{}", fen);
+    }
+    for fen in [
+        // Bishops on opposite colours, two knights, a knight and a bishop
+        "2b1k3/8/8/8/8/8/8/2B1K3 w - - 0 1",
+        "4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1",
+        "4k3/8/8/8/8/8/8/2B1KN2 w - - 0 1",
+        "4k3/8/8/8/8/8/P7/4K3 w - - 0 1",
+        "4k3/8/8/8/8/8/8/R3K3 w - - 0 1",
+    ] {
+        assert_eq!(status(fen), GameStatus::Ongoing, "{}", fen);
+    }
+}
@@ -0,0 +1,46 @@
+use chess::transposition::{Bound, Entry, TranspositionTable};
+use chess::{fen, fen_to_board};
+
+fn entry(depth: usize, score: i32) -> Entry {
+    Entry { depth, score, bound: Bound::Exact, best_move: None }
+}
+
+#[test]
+fn gives_back_what_was_stored() {
+    let table = TranspositionTable::new(1);
+    let game = fen_to_board("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1").unwrap();
+    for movement in game.legal_moves() {
+        for (score, bound) in [(-99_990, Bound::Upper), (0, Bound::Exact), (123, Bound::Lower)] {
+            let stored = Entry { depth: 7, score, bound, best_move: Some(movement) };
+            table.store(game.hash, stored);
+            assert_eq!(table.probe(game.hash), Some(stored));
+        }
+    }
+}
+
+#[test]
+fn misses_other_positions() {
+    let table = TranspositionTable::new(1);
+    let game = fen_to_board(fen::START_FEN).unwrap();
+    assert_eq!(table.probe(game.hash), None);
+    table.store(game.hash, entry(3, 10));
+    // Same slot, different position
+    assert_eq!(table.probe(game.hash + table.capacity() as u64), None);
+    table.clear();
+    assert_eq!(table.probe(game.hash), None);
+}
+
+#[test]
+fn keeps_deeper_entries_from_this_search() {
+    let table = TranspositionTable::new(1);
+    let other = 12345 + table.capacity() as u64;
+    table.new_search();
+    table.store(12345, entry(8, 1));
+    table.store(other, entry(2, 2));
+    assert_eq!(table.probe(12345), Some(entry(8, 1)));
+    // Once a new search starts, old entries make way
+    table.new_search();
+    table.store(other, entry(2, 2));
+    assert_eq!(table.probe(other), Some(entry(2, 2)));
+    assert_eq!(table.probe(12345), None);
+}
@@ -0,0 +1,90 @@
+use std::io::{Cursor, Write};
+use std::sync::{Arc, Mutex};
+
+// Collects what the engine writes so the test can read it afterwards
+#[derive(Clone, Default)]
+struct Output(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn session(input: &str) -> Vec<String> {
+    let output = Output::default();
+    chess::uci::run(Cursor::new(input.to_string()), output.clone());
+    let bytes = output.0.lock().unwrap().clone();
+    String::from_utf8(bytes).unwrap().lines().map(str::to_string).collect()
+}
+
+#[test]
+fn handshake() {
+    let lines = session("uci\nisready\nquit\n");
+    assert!(lines[0].starts_with("id name chess"));
+    assert_eq!(lines[1], "id author The chess contributors");
+    assert_eq!(lines[2], "option name Hash type spin default 16 min 1 max 4096");
+    assert_eq!(lines[3], "option name Book type string default <empty>");
+    assert_eq!(lines[4..], ["uciok", "readyok"]);
+}
+
+#[test]
+fn sets_the_hash_size() {
+    let lines = session("setoption name Hash value 1\nsetoption name Hash value lots\nsetoption name Ponder value true\nisready\nquit\n");
+    assert_eq!(lines, ["info string invalid Hash size lots", "info string no such option Ponder", "readyok"]);
+}
+
+#[test]
+fn plays_from_the_book() {
+    // One entry for the start position: 1. d4, in Polyglot's encoding
+    let mut entry = 0x463b96181691fc9cu64.to_be_bytes().to_vec();
+    entry.extend((3u16 | 3 << 3 | 3 << 6 | 1 << 9).to_be_bytes());
+    entry.extend([0, 1, 0, 0, 0, 0]);
+    let path = std::env::temp_dir().join(format!("uci-book-{}.bin", std::process::id()));
+    std::fs::write(&path, entry).unwrap();
+    let lines = session(&format!("setoption name Book value {}\nposition startpos\ngo depth 3\nquit\n", path.display()));
+    std::fs::remove_file(&path).ok();
+    assert_eq!(lines, ["bestmove d2d4"]);
+}
+
+#[test]
+fn finds_mate_in_one() {
+    let lines = session("position fen 6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1\ngo depth 2\n");
+    assert!(lines.iter().any(|line| line.starts_with("info depth 1 score mate 1 ") && line.ends_with(" pv a1a8")));
+    assert_eq!(lines.last().unwrap(), "bestmove a1a8");
+}
+
+#[test]
+fn plays_moves_from_the_position_command() {
+    // After 1. e4 f6 2. d4 g5 the queen mates on h5
+    let lines = session("ucinewgame\nposition startpos moves e2e4 f7f6 d2d4 g7g5\ngo movetime 5000 depth 1\nquit\n");
+    assert_eq!(lines.last().unwrap(), "bestmove d1h5");
+}
+
+#[test]
+fn null_move_without_legal_moves() {
+    let lines = session("position fen 7k/5Q2/6K1/8/8/8/8/8 b - - 0 1\ngo wtime 1000 btime 1000\n");
+    assert_eq!(lines.last().unwrap(), "bestmove 0000");
+}
+
+#[test]
+fn infinite_search_waits_for_stop() {
+    let lines = session("position fen 6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1\ngo infinite\nisready\nstop\n");
+    assert_eq!(lines.last().unwrap(), "bestmove a1a8");
+    let ready = lines.iter().position(|line| line == "readyok").unwrap();
+    assert!(ready < lines.len() - 1);
+}
+
+#[test]
+fn reports_bad_positions() {
+    let lines = session("position startpos moves e2e5\nposition fen 8/8/8 w\nposition sideways\n");
+    assert_eq!(lines, [
+        "info string can't play e2e5: that move is not allowed",
+        "info string invalid FEN: expected 8 ranks but found 3 at column 5",
+        "info string expected \"position startpos\" or \"position fen <fen>\"",
+    ]);
+}
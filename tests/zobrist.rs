@@ -0,0 +1,67 @@
+use chess::{fen, fen_to_board, parse_movement, zobrist, Game};
+
+fn play(game: &mut Game, moves: &[&str]) {
+    for text in moves {
+        game.move_piece(&parse_movement(text).unwrap()).unwrap();
+    }
+}
+
+#[test]
+fn transpositions_hash_the_same() {
+    let mut knights_first = fen_to_board(fen::START_FEN).unwrap();
+    play(&mut knights_first, &["g1f3", "g8f6", "b1c3", "b8c6"]);
+    let mut other_order = fen_to_board(fen::START_FEN).unwrap();
+    play(&mut other_order, &["b1c3", "b8c6", "g1f3", "g8f6"]);
+    assert_eq!(knights_first.hash, other_order.hash);
+    // And the same as reading the position from FEN
+    let from_fen = fen_to_board(&chess::board_to_fen(&knights_first)).unwrap();
+    assert_eq!(knights_first.hash, from_fen.hash);
+}
+
+#[test]
+fn hash_covers_side_castling_and_en_passant() {
+    let hash = |fen: &str| fen_to_board(fen).unwrap().hash;
+    let base = hash("r3k2r/8/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1");
+    for other in [
+        "r3k2r/8/8/3pP3/8/8/8/R3K2R b KQkq - 0 1",
+        "r3k2r/8/8/3pP3/8/8/8/R3K2R w Kkq d6 0 1",
+        "r3k2r/8/8/3pP3/8/8/8/R3K2R w KQkq - 0 1",
+        "r3k2r/8/8/3pP3/8/8/8/R2K3R w kq d6 0 1",
+    ] {
+        assert_ne!(base, hash(other), "{}", other);
+    }
+    // The clocks don't matter
+    assert_eq!(base, hash("r3k2r/8/8/3pP3/8/8/8/R3K2R w KQkq d6 7 30"));
+}
+
+#[test]
+fn en_passant_only_counts_when_it_can_be_played() {
+    // No white pawn can take on e6, so the square makes no difference
+    assert_eq!(
+        fen_to_board("4k3/8/8/4p3/8/8/8/4K3 w - e6 0 1").unwrap().hash,
+        fen_to_board("4k3/8/8/4p3/8/8/8/4K3 w - - 0 1").unwrap().hash,
+    );
+}
+
+#[test]
+fn hash_follows_moves_and_takebacks() {
+    let mut game = fen_to_board("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1").unwrap();
+    let start = game.hash;
+    let mut undos = vec![];
+    for _ in 0..6 {
+        let movement = game.legal_moves()[0];
+        undos.push(game.make_move(movement));
+        assert_eq!(game.hash, zobrist::hash(&game));
+    }
+    while let Some(undo) = undos.pop() {
+        game.unmake_move(undo);
+    }
+    assert_eq!(game.hash, start);
+}
+
+#[test]
+fn repetitions_are_counted_by_hash() {
+    let mut game = fen_to_board(fen::START_FEN).unwrap();
+    play(&mut game, &["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8"]);
+    assert_eq!(game.repetitions(), 3);
+}